@@ -1,6 +1,52 @@
+use std::collections::HashMap;
 use crate::robots::robot::Robot;
-use crate::maps::map::{Map, Resource, Biome};
+use crate::robots::module::{Bare, Module, ModuleBehaviour};
+use crate::maps::map::{Biome, Map, Resource, Topology};
+use crate::robots::robot::{BATTERY_CAPACITY, SENSOR_RADIUS, START_VISION};
+use crate::robots::class::RobotClass;
+use crate::simulation::config::BaseConfig;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::robots::traffic::Traffic;
+use crate::robots::robot;
+use super::field::ReturnField;
+use super::upgrade::{Tier, UpgradeError};
 
+// Réserve d'énergie d'un avant-poste, remplie à sa construction par l'énergie prélevée sur la base
+pub const OUTPOST_ENERGY_CAPACITY: i32 = 200;
+// Un avant-poste ne vaut la peine qu'au-delà de ce coût de retour en énergie, près d'au moins
+// OUTPOST_CLUSTER gisements connus dans un rayon de OUTPOST_RADIUS cases
+const OUTPOST_DISTANCE: u32 = 30;
+const OUTPOST_CLUSTER: usize = 3;
+const OUTPOST_RADIUS: i32 = 3;
+
+// Que faire de la cargaison qui ne tient pas dans les réserves de la base
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowPolicy {
+    Keep,
+    Wait,
+    Discard,
+}
+
+// Point d'amarrage construit près d'un groupe de gisements éloigné : les robots y déposent, échangent
+// leur carte et se rechargent comme à la base, sur la réserve d'énergie propre à l'avant-poste
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Outpost {
+    pub x: i32,
+    pub y: i32,
+    pub energy: i32,
+}
+
+// Gisement réservé par un robot, que les autres ne ciblent plus
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Claim {
+    pub robot: i32,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Base {
     pub energy_capacity: i32,
     pub energy: i32,
@@ -11,27 +57,98 @@ pub struct Base {
     pub x : i32,
     pub y : i32,
     pub lvl : i32,
+    pub overflow: OverflowPolicy,
+    pub known_map: Map,
+    pub claims: Vec<Claim>,
+    // Robots admis en même temps sur la case de la base quand les collisions sont actives
+    pub docking_slots: i32,
+    // Énergie transférée à un robot à quai à chacune de ses actions (tous les ACTION_TICKS ticks), augmentée par les niveaux qui débloquent `charging`
+    pub charge_rate: i32,
+    // Niveaux suivants de la base, le premier menant au niveau 2
+    pub tiers: Vec<Tier>,
+    pub traffic: Traffic,
+    // Les réserves de fer et de recherche sont communes à la colonie, y compris ce qui est déposé aux avant-postes
+    pub outposts: Vec<Outpost>,
+    pub max_outposts: usize,
+    pub outpost_iron: i32,
+    pub outpost_energy: i32,
+    // Capacités de la colonie, améliorées par la recherche : les robots s'y mettent à jour à quai
+    pub battery_capacity: i32,
+    pub sensor_radius: usize,
+    pub upgrade_discount: i32,
+    pub locked_modules: Vec<Module>,
+    pub locked_classes: Vec<RobotClass>,
+    // Champs de retour par équipement, recalculés seulement quand la carte de la base révèle du terrain
+    #[serde(skip)]
+    return_fields: HashMap<(Option<Module>, Topology), ReturnField>,
+    // Incrémenté à chaque fois que ces champs sont à refaire : les robots savent ainsi si leur prolongement est à jour
+    #[serde(skip)]
+    fields_revision: u64,
 }
 
 impl Base {
-    pub fn new(map_width: i32, map_height: i32) -> Self {
+    pub fn new(map: &Map, settings: &BaseConfig) -> Self {
+        let x = map.width / 2;
+        let y = map.height / 2;
+        let mut known_map = Map::unknown(map.seed, map.width, map.height);
+        known_map.discover_area(map, x as usize, y as usize, START_VISION, 0);
         Self {
-            energy_capacity: 500,
-            energy: 250,
-            iron_storage: 100,
-            iron: 0,
-            research_storage: 20,
-            research: 0,
-            x: map_width / 2,
-            y: map_height / 2,
+            energy_capacity: settings.energy_capacity,
+            energy: settings.energy,
+            iron_storage: settings.iron_storage,
+            iron: settings.iron,
+            research_storage: settings.research_storage,
+            research: settings.research,
+            x,
+            y,
             lvl: 1,
+            overflow: settings.overflow,
+            known_map,
+            claims: Vec::new(),
+            docking_slots: settings.docking_slots,
+            charge_rate: settings.charge_rate,
+            tiers: settings.tiers.clone(),
+            traffic: Traffic::default(),
+            outposts: Vec::new(),
+            max_outposts: settings.max_outposts,
+            outpost_iron: settings.outpost_iron,
+            outpost_energy: settings.outpost_energy,
+            battery_capacity: BATTERY_CAPACITY,
+            sensor_radius: SENSOR_RADIUS,
+            upgrade_discount: 0,
+            locked_modules: Vec::new(),
+            locked_classes: Vec::new(),
+            return_fields: HashMap::new(),
+            fields_revision: 0,
         }
     }
 
+    // Cases où un robot peut s'amarrer : la base puis ses avant-postes
+    pub fn docks(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        std::iter::once((self.x, self.y)).chain(self.outposts.iter().map(|outpost| (outpost.x, outpost.y)))
+    }
+
+    pub fn is_dock(&self, cell: (i32, i32)) -> bool {
+        self.docks().any(|dock| dock == cell)
+    }
+
+    // Réserve d'énergie du point d'amarrage situé sur la case
+    fn energy_at(&mut self, (x, y): (i32, i32)) -> Option<&mut i32> {
+        if (x, y) == (self.x, self.y) {
+            return Some(&mut self.energy);
+        }
+        self.outposts.iter_mut().find(|outpost| (outpost.x, outpost.y) == (x, y)).map(|outpost| &mut outpost.energy)
+    }
+
     pub fn recharge_robot(&mut self, robot: &mut Robot) {
-        if self.energy > 0 {
-            robot.energy += 5;
-            self.energy -= 5;
+        let charge_rate = self.charge_rate;
+        let Some(energy) = self.energy_at((robot.x, robot.y)) else {
+            return;
+        };
+        let amount = charge_rate.min(*energy).min(robot.battery - robot.energy);
+        if amount > 0 {
+            robot.energy += amount;
+            *energy -= amount;
         }
     }
 
@@ -39,22 +156,127 @@ impl Base {
         if self.energy < self.energy_capacity {
             self.energy += 1;
         }
+        for outpost in self.outposts.iter_mut() {
+            if outpost.energy < OUTPOST_ENERGY_CAPACITY {
+                outpost.energy += 1;
+            }
+        }
+    }
+
+    // Construit un avant-poste à côté du gisement où mine le robot s'il est loin de tout point d'amarrage et entouré
+    // d'autres gisements, en prélevant du fer sur les réserves et de l'énergie sur la base. Renvoie la case choisie
+    pub fn build_outpost(&mut self, robot: &Robot) -> Option<(i32, i32)> {
+        if self.outposts.len() >= self.max_outposts || self.iron < self.outpost_iron || self.energy < self.outpost_energy {
+            return None;
+        }
+        let map = &robot.known_map;
+        let here = map.blueprint[robot.x as usize][robot.y as usize].resource;
+        if here == Resource::None || robot.class.behaviour().target_resource() != Some(here) {
+            return None;
+        }
+        if robot.return_cost(self).is_some_and(|cost| cost < OUTPOST_DISTANCE) {
+            return None;
+        }
+        let (min_x, max_x) = ((robot.x - OUTPOST_RADIUS).max(0), (robot.x + OUTPOST_RADIUS).min(map.width - 1));
+        let (min_y, max_y) = ((robot.y - OUTPOST_RADIUS).max(0), (robot.y + OUTPOST_RADIUS).min(map.height - 1));
+        let deposits = (min_x..=max_x)
+            .flat_map(|x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter(|&(x, y)| map.blueprint[x as usize][y as usize].resource != Resource::None)
+            .count();
+        if deposits < OUTPOST_CLUSTER {
+            return None;
+        }
+        let (x, y) = robot::neighbors(robot.module(), robot.topology, (robot.x, robot.y), map, false)
+            .into_iter()
+            .map(|(cell, _)| cell)
+            .find(|&(x, y)| map.blueprint[x as usize][y as usize].resource == Resource::None && !self.is_dock((x, y)))?;
+
+        self.iron -= self.outpost_iron;
+        self.energy -= self.outpost_energy;
+        self.outposts.push(Outpost { x, y, energy: self.outpost_energy });
+        // Le champ de retour est calculé sur la carte de la base : elle doit connaître les abords de l'avant-poste
+        self.known_map.merge(&robot.known_map);
+        self.forget_return_fields();
+        Some((x, y))
     }
 
     pub fn deposit_resources(&mut self, robot: &mut Robot) {
-        if robot.iron_collected > 0 {
-            self.iron += robot.iron_collected;
+        let iron = robot.iron_collected.min(self.iron_storage - self.iron).max(0);
+        self.iron += iron;
+        robot.iron_collected -= iron;
+
+        let research = robot.research_collected.min(self.research_storage - self.research).max(0);
+        self.research += research;
+        robot.research_collected -= research;
+
+        if self.overflow == OverflowPolicy::Discard {
             robot.iron_collected = 0;
-        }
-        if robot.research_collected > 0 {
-            self.research += robot.research_collected;
             robot.research_collected = 0;
         }
     }
 
-    pub fn share_map(&self, robot: &mut Robot) {
+    // Avec la politique Wait, un robot dont la cargaison ne tient pas reste à quai
+    pub fn must_wait(&self, robot: &Robot) -> bool {
+        self.overflow == OverflowPolicy::Wait && (robot.iron_collected > 0 || robot.research_collected > 0)
+    }
+
+    // Avec la politique Keep, un robot plein que la base ne peut pas décharger repart explorer avec sa cargaison
+    // au lieu d'occuper un quai, et ne rentre qu'une fois de la place libérée dans les réserves
+    pub fn holds_out(&self, robot: &Robot) -> bool {
+        let room = (robot.iron_collected > 0 && self.iron < self.iron_storage)
+            || (robot.research_collected > 0 && self.research < self.research_storage);
+        self.overflow == OverflowPolicy::Keep && !room
+    }
+
+    // Fusionne les découvertes du robot dans la carte de la base, puis lui transmet celles des autres
+    pub fn share_map(&mut self, robot: &mut Robot) {
+        if self.known_map.merge(&robot.known_map) {
+            self.forget_return_fields();
+        }
+        robot.known_map.clone_from(&self.known_map);
+    }
+
+    // Remplace la réservation du robot par la nouvelle cible, ou la libère s'il n'en a plus
+    pub fn claim(&mut self, robot: i32, target: Option<(i32, i32)>) {
+        self.claims.retain(|claim| claim.robot != robot);
+        if let Some((x, y)) = target {
+            self.claims.push(Claim { robot, x, y });
+        }
+    }
+
+    pub fn is_claimed(&self, (x, y): (i32, i32), robot: i32) -> bool {
+        self.claims.iter().any(|claim| claim.x == x && claim.y == y && claim.robot != robot)
+    }
+
+    // Un gisement épuisé n'a plus rien à réserver
+    pub fn release_at(&mut self, (x, y): (i32, i32)) {
+        self.claims.retain(|claim| claim.x != x || claim.y != y);
+    }
+
+    fn forget_return_fields(&mut self) {
+        self.return_fields.clear();
+        self.fields_revision += 1;
+    }
+
+    pub fn fields_revision(&self) -> u64 {
+        self.fields_revision
+    }
+
+    pub fn prepare_return_field(&mut self, module: Option<Module>, topology: Topology) {
+        if self.return_fields.contains_key(&(module, topology)) {
+            return;
+        }
+        let behaviour = module.map_or(&Bare as &dyn ModuleBehaviour, |module| module.behaviour());
+        let docks: Vec<(i32, i32)> = self.docks().collect();
+        let field = ReturnField::compute(&self.known_map, behaviour, topology, &docks);
+        self.return_fields.insert((module, topology), field);
+    }
+
+    pub fn return_field(&self, module: Option<Module>, topology: Topology) -> Option<&ReturnField> {
+        self.return_fields.get(&(module, topology))
     }
 
+    // Équipement choisi d'après le terrain autour du point d'amarrage où se trouve le robot
     pub fn modify_robot_equipment(&mut self, robot: &mut Robot) {
         let radius = 10;
         let blueprint = &robot.known_map.blueprint;
@@ -64,10 +286,10 @@ impl Base {
         }
         let map_height = blueprint[0].len();
 
-        let base_x = self.x as usize;
-        let base_y = self.y as usize;
-        let start_x = if base_x >= radius { base_x - radius } else { 0 };
-        let start_y = if base_y >= radius { base_y - radius } else { 0 };
+        let base_x = robot.x as usize;
+        let base_y = robot.y as usize;
+        let start_x = base_x.saturating_sub(radius);
+        let start_y = base_y.saturating_sub(radius);
         let end_x = usize::min(base_x + radius, map_width - 1);
         let end_y = usize::min(base_y + radius, map_height - 1);
 
@@ -75,9 +297,9 @@ impl Base {
         let mut mountain_count = 0;
         let mut others_count = 0;
 
-        for i in start_x..=end_x {
-            for j in start_y..=end_y {
-                match blueprint[i][j].biome {
+        for column in &blueprint[start_x..=end_x] {
+            for tile in &column[start_y..=end_y] {
+                match tile.biome {
                     Biome::Water => water_count += 1,
                     Biome::Mountain => mountain_count += 1,
                     _ => others_count += 1,
@@ -85,25 +307,162 @@ impl Base {
             }
         }
 
-        if water_count >= mountain_count && water_count >= others_count {
-            robot.modules = Some("buoy".to_string());
+        // Équipement préféré pour ce terrain, puis repli sur les roues, parmi ceux que la recherche a débloqués
+        let preferred = if water_count >= mountain_count && water_count >= others_count {
+            [Module::Buoy, Module::Wheels]
         } else if mountain_count >= water_count && mountain_count >= others_count {
-            robot.modules = Some("tracks".to_string());
+            [Module::Tracks, Module::Wheels]
         } else {
-            robot.modules = Some("wheels".to_string());
+            [Module::Wheels, Module::Wheels]
+        };
+        robot.modules = preferred.into_iter().find(|module| !self.locked_modules.contains(module));
+    }
+
+    // Met la batterie et les capteurs du robot au niveau de ce que la recherche a débloqué
+    pub fn refit(&self, robot: &mut Robot) {
+        robot.battery = robot.battery.max(self.battery_capacity);
+        robot.sensor_radius = robot.sensor_radius.max(self.sensor_radius);
+    }
+
+    // Prochain niveau de la base, s'il en reste un
+    pub fn next_tier(&self) -> Option<&Tier> {
+        usize::try_from(self.lvl - 1).ok().and_then(|index| self.tiers.get(index))
+    }
+
+    // Paie le prochain niveau sur les réserves et en applique les effets. Renvoie le nouveau niveau
+    pub fn upgrade_base(&mut self) -> Result<i32, UpgradeError> {
+        let tier = self.next_tier().ok_or(UpgradeError::MaxLevel(self.lvl))?.clone();
+        let (energy, iron, research) = tier.cost(self.upgrade_discount);
+        if self.energy < energy || self.iron < iron || self.research < research {
+            return Err(UpgradeError::NotEnough { lvl: self.lvl + 1, energy, iron, research });
         }
+        self.energy -= energy;
+        self.iron -= iron;
+        self.research -= research;
+        self.lvl += 1;
+        tier.apply(self);
+        Ok(self.lvl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::map::{generate_map, TileInfo};
+    use crate::base::upgrade::Capability;
+
+    fn docked(overflow: OverflowPolicy) -> (Base, Robot) {
+        let (map, _) = generate_map(1, 20, 20);
+        let settings = BaseConfig { iron_storage: 10, iron: 6, research_storage: 10, research: 0, overflow, ..BaseConfig::default() };
+        let mut robot = Robot::new(map.width, map.height, &map, RobotClass::Miner);
+        robot.iron_collected = 7;
+        robot.research_collected = 3;
+        (Base::new(&map, &settings), robot)
     }
 
-    pub fn upgrade_base(&mut self) {
-        if self.energy >= 100 && self.iron >= 10 {
-            self.energy -= 100;
-            self.iron -= 10;
-            self.lvl += 1;
-            self.energy_capacity += 250;
-            self.iron_storage += 50;
-            self.research_storage += 20;
-        } else {
-            println!("Not enough resources to upgrade the base.");
+    #[test]
+    fn deposits_stop_at_the_storage_limit() {
+        let (mut base, mut robot) = docked(OverflowPolicy::Keep);
+        base.deposit_resources(&mut robot);
+        assert_eq!((base.iron, base.research), (10, 3));
+        assert_eq!((robot.iron_collected, robot.research_collected), (3, 0));
+        assert!(base.holds_out(&robot));
+        assert!(!base.must_wait(&robot));
+    }
+
+    #[test]
+    fn overflow_policy_decides_what_happens_to_the_rest() {
+        let (mut base, mut robot) = docked(OverflowPolicy::Wait);
+        base.deposit_resources(&mut robot);
+        assert_eq!(robot.iron_collected, 3);
+        assert!(base.must_wait(&robot));
+
+        let (mut base, mut robot) = docked(OverflowPolicy::Discard);
+        base.deposit_resources(&mut robot);
+        assert_eq!(base.iron, 10);
+        assert_eq!((robot.iron_collected, robot.research_collected), (0, 0));
+    }
+
+    #[test]
+    fn claims_are_exclusive_and_replaced_by_the_next_target() {
+        let (mut base, _) = docked(OverflowPolicy::Keep);
+        base.claim(1, Some((3, 4)));
+        assert!(base.is_claimed((3, 4), 2));
+        assert!(!base.is_claimed((3, 4), 1));
+
+        base.claim(1, Some((5, 5)));
+        assert!(!base.is_claimed((3, 4), 2));
+        assert!(base.is_claimed((5, 5), 2));
+
+        base.claim(2, Some((6, 6)));
+        base.release_at((5, 5));
+        base.claim(2, None);
+        assert!(base.claims.is_empty());
+    }
+
+    #[test]
+    fn robots_skip_deposits_claimed_by_others() {
+        let (mut base, mut robot) = docked(OverflowPolicy::Keep);
+        for column in robot.known_map.blueprint.iter_mut() {
+            for tile in column.iter_mut() {
+                *tile = TileInfo { biome: Biome::Plain, resource: Resource::None, quantity: 0, richness: 0, seen: 0 };
+            }
+        }
+        let (x, y) = (robot.x, robot.y);
+        for cell in [(x + 1, y), (x + 4, y)] {
+            robot.known_map.blueprint[cell.0 as usize][cell.1 as usize] = TileInfo { biome: Biome::Plain, resource: Resource::Iron, quantity: 10, richness: 2, seen: 0 };
         }
+        let target = |robot: &Robot, base: &Base| robot.path_to_resource(Resource::Iron, base).and_then(|(path, _)| path.last().copied());
+        assert_eq!(target(&robot, &base), Some((x + 1, y)));
+
+        base.claim(robot.id + 1, Some((x + 1, y)));
+        assert_eq!(target(&robot, &base), Some((x + 4, y)));
+    }
+
+    fn upgradable(research: i32) -> Base {
+        let (map, _) = generate_map(1, 20, 20);
+        let tier = |energy, iron, research, energy_capacity, unlock| Tier {
+            energy, iron, research, energy_capacity, iron_storage: 0, research_storage: 0, unlock,
+        };
+        let tiers = vec![
+            tier(100, 10, 0, 250, None),
+            tier(40, 20, 10, 0, Some(Capability::Docking { slots: 1 })),
+        ];
+        let settings = BaseConfig { energy_capacity: 500, energy: 300, iron_storage: 50, iron: 40, research_storage: 20, research, tiers, ..BaseConfig::default() };
+        Base::new(&map, &settings)
+    }
+
+    #[test]
+    fn upgrades_pay_their_cost_and_apply_their_tier() {
+        let mut base = upgradable(10);
+        let slots = base.docking_slots;
+        assert_eq!(base.upgrade_base().ok(), Some(2));
+        assert_eq!((base.energy, base.iron, base.research, base.energy_capacity), (200, 30, 10, 750));
+        assert_eq!(base.upgrade_base().ok(), Some(3));
+        assert_eq!((base.energy, base.iron, base.research), (160, 10, 0));
+        assert_eq!(base.docking_slots, slots + 1);
+        assert!(matches!(base.upgrade_base(), Err(UpgradeError::MaxLevel(3))));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn missing_resources_refuse_the_upgrade_untouched() {
+        let mut base = upgradable(5);
+        base.upgrade_base().expect("the first tier is affordable");
+        let before = (base.energy, base.iron, base.research, base.lvl);
+        assert!(matches!(
+            base.upgrade_base(),
+            Err(UpgradeError::NotEnough { lvl: 3, energy: 40, iron: 20, research: 10 })
+        ));
+        assert_eq!((base.energy, base.iron, base.research, base.lvl), before);
+    }
+
+    #[test]
+    fn research_discount_lowers_every_cost() {
+        let mut base = upgradable(5);
+        base.upgrade_base().expect("the first tier is affordable");
+        base.upgrade_discount = 50;
+        assert_eq!(base.next_tier().map(|tier| tier.cost(base.upgrade_discount)), Some((20, 10, 5)));
+        assert_eq!(base.upgrade_base().ok(), Some(3));
+        assert_eq!((base.energy, base.iron, base.research), (180, 20, 0));
+    }
+}
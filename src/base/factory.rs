@@ -0,0 +1,165 @@
+use std::{collections::VecDeque, error::Error, fmt};
+use serde::{Deserialize, Serialize};
+use crate::maps::map::Topology;
+use crate::robots::class::RobotClass;
+use crate::robots::energy::EnergyCosts;
+use crate::robots::strategy::StrategyKind;
+use crate::simulation::config::Config;
+use super::base::Base;
+
+// Robot commandé à l'usine de la base, payé à la commande et construit quand vient son tour
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Order {
+    pub class: RobotClass,
+    pub ticks_left: u32,
+}
+
+#[derive(Debug)]
+pub enum FactoryError {
+    Locked(RobotClass),
+    QueueFull(usize),
+    NotEnough { iron: i32, energy: i32 },
+}
+
+impl fmt::Display for FactoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FactoryError::Locked(class) => write!(f, "{} robots must be researched first", class),
+            FactoryError::QueueFull(size) => write!(f, "build queue is full ({} orders)", size),
+            FactoryError::NotEnough { iron, energy } => write!(f, "a robot costs {} iron and {} energy", iron, energy),
+        }
+    }
+}
+
+impl Error for FactoryError {}
+
+// File de construction de la base, et réglages donnés aux robots qu'elle produit
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Factory {
+    pub queue: VecDeque<Order>,
+    pub iron: i32,
+    pub energy: i32,
+    pub build_ticks: u32,
+    pub max_queue: usize,
+    // Les nouveaux robots reçoivent à tour de rôle les stratégies de la configuration, comme ceux du départ
+    pub strategies: Vec<StrategyKind>,
+    pub topology: Topology,
+    pub collisions: bool,
+    pub energy_costs: EnergyCosts,
+    pub produced: usize,
+}
+
+impl Factory {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            iron: config.factory.iron,
+            energy: config.factory.energy,
+            build_ticks: config.factory.build_ticks,
+            max_queue: config.factory.queue,
+            strategies: config.strategies.clone(),
+            topology: config.topology,
+            collisions: config.collisions,
+            energy_costs: config.energy,
+            produced: config.num_robots,
+        }
+    }
+
+    // Ajoute la commande si la file a de la place et que la base peut la payer, en prélevant son coût
+    pub fn order(&mut self, class: RobotClass, base: &mut Base) -> Result<(), FactoryError> {
+        if base.locked_classes.contains(&class) {
+            return Err(FactoryError::Locked(class));
+        }
+        if self.queue.len() >= self.max_queue {
+            return Err(FactoryError::QueueFull(self.max_queue));
+        }
+        if base.iron < self.iron || base.energy < self.energy {
+            return Err(FactoryError::NotEnough { iron: self.iron, energy: self.energy });
+        }
+        base.iron -= self.iron;
+        base.energy -= self.energy;
+        self.queue.push_back(Order { class, ticks_left: self.build_ticks });
+        Ok(())
+    }
+
+    // Avance la commande en tête de file d'un tick, et renvoie la classe du robot terminé s'il y en a un.
+    // Un robot terminé reste à l'usine tant qu'il n'a pas de place à quai
+    pub fn advance(&mut self, docking: bool) -> Option<RobotClass> {
+        let order = self.queue.front_mut()?;
+        order.ticks_left = order.ticks_left.saturating_sub(1);
+        if order.ticks_left > 0 || !docking {
+            return None;
+        }
+        self.queue.pop_front().map(|order| order.class)
+    }
+
+    pub fn next_strategy(&mut self) -> StrategyKind {
+        let strategy = self.strategies[self.produced % self.strategies.len()];
+        self.produced += 1;
+        strategy
+    }
+
+    // Ticks restants avant la livraison de chaque commande, en comptant celles qui la précèdent
+    pub fn delivery_ticks(&self) -> Vec<(RobotClass, u32)> {
+        let mut total = 0;
+        self.queue.iter().map(|order| {
+            total += order.ticks_left;
+            (order.class, total)
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::map::generate_map;
+    use crate::simulation::config::{BaseConfig, FactoryConfig};
+
+    fn setup(iron: i32, energy: i32) -> (Factory, Base) {
+        let config = Config { factory: FactoryConfig { iron: 10, energy: 50, build_ticks: 3, queue: 2 }, ..Config::default() };
+        let (map, _) = generate_map(1, 20, 20);
+        let base = Base::new(&map, &BaseConfig { iron_storage: 100, iron, energy, ..BaseConfig::default() });
+        (Factory::new(&config), base)
+    }
+
+    #[test]
+    fn orders_are_paid_when_placed() {
+        let (mut factory, mut base) = setup(25, 200);
+        factory.order(RobotClass::Miner, &mut base).expect("the order is affordable");
+        assert_eq!((base.iron, base.energy), (15, 150));
+        assert_eq!(factory.queue.len(), 1);
+    }
+
+    #[test]
+    fn orders_are_refused_untouched() {
+        let (mut factory, mut base) = setup(25, 200);
+        base.locked_classes.push(RobotClass::Explorer);
+        assert!(matches!(factory.order(RobotClass::Explorer, &mut base), Err(FactoryError::Locked(RobotClass::Explorer))));
+
+        factory.order(RobotClass::Miner, &mut base).expect("the first order is affordable");
+        factory.order(RobotClass::Miner, &mut base).expect("the second order is affordable");
+        assert!(matches!(factory.order(RobotClass::Miner, &mut base), Err(FactoryError::QueueFull(2))));
+        assert_eq!((base.iron, base.energy), (5, 100));
+
+        factory.queue.clear();
+        assert!(matches!(factory.order(RobotClass::Miner, &mut base), Err(FactoryError::NotEnough { iron: 10, energy: 50 })));
+        assert_eq!((base.iron, base.energy), (5, 100));
+        assert!(factory.queue.is_empty());
+    }
+
+    #[test]
+    fn finished_robots_wait_for_a_dock_slot() {
+        let (mut factory, mut base) = setup(25, 200);
+        factory.order(RobotClass::Scientist, &mut base).expect("the first order is affordable");
+        factory.order(RobotClass::Miner, &mut base).expect("the second order is affordable");
+        assert_eq!(factory.delivery_ticks(), vec![(RobotClass::Scientist, 3), (RobotClass::Miner, 6)]);
+
+        assert_eq!(factory.advance(true), None);
+        assert_eq!(factory.advance(true), None);
+        assert_eq!(factory.advance(false), None);
+        assert_eq!(factory.advance(false), None);
+        assert_eq!(factory.delivery_ticks(), vec![(RobotClass::Scientist, 0), (RobotClass::Miner, 3)]);
+        assert_eq!(factory.advance(true), Some(RobotClass::Scientist));
+        assert_eq!(factory.delivery_ticks(), vec![(RobotClass::Miner, 3)]);
+    }
+}
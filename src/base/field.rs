@@ -0,0 +1,168 @@
+use pathfinding::prelude::dijkstra_all;
+use crate::maps::map::{Biome, Map, Topology};
+use crate::robots::module::ModuleBehaviour;
+use crate::robots::robot;
+
+// Champ de retour : pour chaque case connue de la base, coût du trajet jusqu'au point d'amarrage le plus proche
+// (la base ou un avant-poste) et prochaine case à prendre
+#[derive(Clone)]
+pub struct ReturnField {
+    height: i32,
+    steps: Vec<Option<(u32, (i32, i32))>>,
+}
+
+impl ReturnField {
+    // Dijkstra inversé depuis tous les points d'amarrage à la fois, reliés à une origine fictive (None) sans coût :
+    // passer de `previous` à `cell` coûte l'entrée sur `cell`
+    pub fn compute(map: &Map, module: &dyn ModuleBehaviour, topology: Topology, docks: &[(i32, i32)]) -> Self {
+        let reached = dijkstra_all(&None, |&node: &Option<(i32, i32)>| -> Vec<(Option<(i32, i32)>, u32)> {
+            let Some(cell) = node else {
+                return docks.iter().map(|&dock| (Some(dock), 0)).collect();
+            };
+            let Some(entry) = robot::enter_cost(module, cell, map, false) else {
+                return Vec::new();
+            };
+            robot::neighbors(module, topology, cell, map, false)
+                .into_iter()
+                .map(|(previous, _)| {
+                    let delta = (cell.0 - previous.0, cell.1 - previous.1);
+                    (Some(previous), Topology::step_cost(entry, delta))
+                })
+                .collect()
+        });
+
+        let mut steps = vec![None; (map.width * map.height) as usize];
+        for (&node, &(next, cost)) in &reached {
+            if let Some((x, y)) = node {
+                steps[(x * map.height + y) as usize] = Some((cost, next.unwrap_or((x, y))));
+            }
+        }
+        Self { height: map.height, steps }
+    }
+
+    // Prolonge le champ sur une carte plus complète (celle d'un robot) : les cases déjà couvertes gardent leur coût
+    // et servent de points de départ aux nouvelles, sans refaire la propagation sur ce que la base connaît
+    pub fn extend(&self, map: &Map, module: &dyn ModuleBehaviour, topology: Topology) -> Self {
+        let uncovered = |(x, y): (i32, i32)| {
+            self.step((x, y)).is_none() && map.blueprint[x as usize][y as usize].biome != Biome::Unknown
+        };
+        let mut border = Vec::new();
+        for x in 0..map.width {
+            for y in 0..map.height {
+                if !uncovered((x, y)) {
+                    continue;
+                }
+                for (cell, _) in robot::neighbors(module, topology, (x, y), map, false) {
+                    if let Some(cost) = self.cost(cell) {
+                        border.push((cell, cost));
+                    }
+                }
+            }
+        }
+
+        let reached = dijkstra_all(&None, |&node: &Option<(i32, i32)>| -> Vec<(Option<(i32, i32)>, u32)> {
+            let Some(cell) = node else {
+                return border.iter().map(|&(cell, cost)| (Some(cell), cost)).collect();
+            };
+            let Some(entry) = robot::enter_cost(module, cell, map, false) else {
+                return Vec::new();
+            };
+            robot::neighbors(module, topology, cell, map, false)
+                .into_iter()
+                .filter(|&(previous, _)| uncovered(previous))
+                .map(|(previous, _)| {
+                    let delta = (cell.0 - previous.0, cell.1 - previous.1);
+                    (Some(previous), Topology::step_cost(entry, delta))
+                })
+                .collect()
+        });
+
+        let mut extended = self.clone();
+        for (&node, &(next, cost)) in &reached {
+            if let (Some((x, y)), Some(next)) = (node, next) {
+                if uncovered((x, y)) {
+                    extended.steps[(x * self.height + y) as usize] = Some((cost, next));
+                }
+            }
+        }
+        extended
+    }
+
+    fn step(&self, (x, y): (i32, i32)) -> Option<(u32, (i32, i32))> {
+        self.steps.get((x * self.height + y) as usize).copied().flatten()
+    }
+
+    pub fn cost(&self, cell: (i32, i32)) -> Option<u32> {
+        self.step(cell).map(|(cost, _)| cost)
+    }
+
+    pub fn next_step(&self, cell: (i32, i32)) -> Option<(i32, i32)> {
+        self.step(cell).map(|(_, next)| next).filter(|&next| next != cell)
+    }
+
+    // Chemin complet vers le point d'amarrage le plus proche, case de départ comprise, comme ceux renvoyés par A*
+    pub fn path(&self, start: (i32, i32)) -> Option<(Vec<(i32, i32)>, u32)> {
+        let cost = self.cost(start)?;
+        let mut path = vec![start];
+        let mut cell = start;
+        while let Some(next) = self.next_step(cell) {
+            path.push(next);
+            cell = next;
+        }
+        Some((path, cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::robots::module::Bare;
+
+    // Carte connue sur les `known` premières colonnes : plaine (coût 2 sans équipement), montagnes aux cases données
+    fn map(width: i32, height: i32, known: i32, mountains: &[(i32, i32)]) -> Map {
+        let mut map = Map::unknown(0, width, height);
+        for (x, column) in map.blueprint.iter_mut().enumerate().take(known as usize) {
+            for (y, tile) in column.iter_mut().enumerate() {
+                let mountain = mountains.contains(&(x as i32, y as i32));
+                tile.biome = if mountain { Biome::Mountain } else { Biome::Plain };
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn costs_and_steps_lead_to_the_nearest_dock() {
+        let field = ReturnField::compute(&map(7, 2, 7, &[(3, 1)]), &Bare, Topology::Four, &[(0, 0), (6, 0)]);
+        assert_eq!(field.cost((0, 0)), Some(0));
+        assert_eq!(field.next_step((0, 0)), None);
+        assert_eq!((field.cost((2, 0)), field.next_step((2, 0))), (Some(4), Some((1, 0))));
+        assert_eq!((field.cost((4, 0)), field.next_step((4, 0))), (Some(4), Some((5, 0))));
+        assert_eq!(field.cost((3, 0)), Some(6));
+        let (path, cost) = field.path((5, 1)).expect("both docks are reachable");
+        assert_eq!((path.len(), path.last(), cost), (3, Some(&(6, 0)), 4));
+    }
+
+    #[test]
+    fn impassable_and_unknown_cells_are_not_covered() {
+        let field = ReturnField::compute(&map(7, 2, 5, &[(3, 1)]), &Bare, Topology::Four, &[(0, 0)]);
+        assert_eq!(field.cost((3, 1)), None);
+        assert_eq!(field.cost((5, 0)), None);
+        assert_eq!(field.path((3, 1)), None);
+    }
+
+    #[test]
+    fn extension_chains_back_into_covered_cells() {
+        let base = ReturnField::compute(&map(7, 2, 4, &[]), &Bare, Topology::Four, &[(0, 0)]);
+        let robot = map(7, 2, 7, &[(5, 1)]);
+        let extended = base.extend(&robot, &Bare, Topology::Four);
+
+        // Les cases couvertes gardent leur coût, les nouvelles y reviennent au coût du chemin
+        assert_eq!(extended.cost((3, 0)), base.cost((3, 0)));
+        assert_eq!(extended.next_step((4, 0)), Some((3, 0)));
+        assert_eq!(extended.cost((6, 0)), Some(base.cost((3, 0)).unwrap() + 6));
+        let (path, cost) = extended.path((6, 1)).expect("the new cell leads home");
+        assert_eq!(path.last(), Some(&(0, 0)));
+        assert_eq!(cost, 14);
+        assert_eq!(extended.cost((5, 1)), None);
+    }
+}
@@ -1 +1,5 @@
-pub mod base;
\ No newline at end of file
+pub mod base;
+pub mod factory;
+pub mod field;
+pub mod research;
+pub mod upgrade;
\ No newline at end of file
@@ -0,0 +1,300 @@
+use std::{error::Error, fmt};
+use serde::{Deserialize, Serialize};
+use crate::robots::class::RobotClass;
+use crate::robots::module::Module;
+use super::base::Base;
+
+// Arbre utilisé quand la configuration n'en désigne pas d'autre
+pub const DEFAULT_TECH_TREE: &str = include_str!("../../techs.toml");
+// Points de recherche investis par tick dans le projet en cours
+const RESEARCH_RATE: i32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Effect {
+    Battery { amount: i32 },
+    Sensors { radius: usize },
+    Upgrades { discount: i32 },
+    Module { module: Module },
+    Class { class: RobotClass },
+}
+
+impl Effect {
+    fn apply(&self, base: &mut Base) {
+        match *self {
+            Effect::Battery { amount } => base.battery_capacity += amount,
+            Effect::Sensors { radius } => base.sensor_radius += radius,
+            Effect::Upgrades { discount } => base.upgrade_discount = (base.upgrade_discount + discount).min(100),
+            Effect::Module { module } => base.locked_modules.retain(|&locked| locked != module),
+            Effect::Class { class } => base.locked_classes.retain(|&locked| locked != class),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tech {
+    pub id: String,
+    pub name: String,
+    pub cost: i32,
+    #[serde(default)]
+    pub requires: Vec<String>,
+    pub effect: Effect,
+    // Retire l'équipement ou la classe débloqué par le projet tant qu'il n'est pas terminé
+    #[serde(default)]
+    pub locks: bool,
+}
+
+// Contenu d'un fichier d'arbre de recherche : une table [[tech]] par projet
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TechTree {
+    pub tech: Vec<Tech>,
+}
+
+impl Default for TechTree {
+    fn default() -> Self {
+        toml::from_str(DEFAULT_TECH_TREE).expect("the built-in tech tree is valid TOML")
+    }
+}
+
+impl TechTree {
+    // Identifiants uniques, coûts positifs, prérequis déjà définis plus haut dans le fichier (donc sans cycle)
+    pub fn check(&self) -> Result<(), String> {
+        for (i, tech) in self.tech.iter().enumerate() {
+            let earlier = &self.tech[..i];
+            if earlier.iter().any(|other| other.id == tech.id) {
+                return Err(format!("tech '{}' is defined twice", tech.id));
+            }
+            if tech.cost <= 0 {
+                return Err(format!("tech '{}' must have a positive cost", tech.id));
+            }
+            if let Some(missing) = tech.requires.iter().find(|id| !earlier.iter().any(|other| &&other.id == id)) {
+                return Err(format!("tech '{}' requires '{}', which must be defined before it", tech.id, missing));
+            }
+        }
+        Ok(())
+    }
+
+    // Classe retirée de l'usine au départ par un projet qui la débloque
+    pub fn locks_class(&self, class: RobotClass) -> bool {
+        self.tech.iter().any(|tech| tech.locks && matches!(tech.effect, Effect::Class { class: unlocked } if unlocked == class))
+    }
+}
+
+#[derive(Debug)]
+pub enum ResearchError {
+    Unknown(usize),
+    Done(String),
+    Locked(String, Vec<String>),
+}
+
+impl fmt::Display for ResearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResearchError::Unknown(index) => write!(f, "no research project #{}", index),
+            ResearchError::Done(name) => write!(f, "{} is already researched", name),
+            ResearchError::Locked(name, missing) => write!(f, "{} requires {}", name, missing.join(", ")),
+        }
+    }
+}
+
+impl Error for ResearchError {}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TechState {
+    Done,
+    Active,
+    Available,
+    Locked,
+}
+
+// Avancement de la colonie dans l'arbre : points déjà investis dans chaque projet, et projet en cours
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Research {
+    pub techs: Vec<Tech>,
+    pub progress: Vec<i32>,
+    pub active: Option<usize>,
+}
+
+impl Research {
+    pub fn new(tree: TechTree) -> Self {
+        let progress = vec![0; tree.tech.len()];
+        Self { techs: tree.tech, progress, active: None }
+    }
+
+    // Les équipements et classes débloqués par un projet verrouillant restent indisponibles tant qu'il n'est pas terminé
+    pub fn lock(&self, base: &mut Base) {
+        for (index, tech) in self.techs.iter().enumerate() {
+            if !tech.locks || self.is_done(index) {
+                continue;
+            }
+            match tech.effect {
+                Effect::Module { module } if !base.locked_modules.contains(&module) => base.locked_modules.push(module),
+                Effect::Class { class } if !base.locked_classes.contains(&class) => base.locked_classes.push(class),
+                _ => {},
+            }
+        }
+    }
+
+    fn is_done(&self, index: usize) -> bool {
+        self.progress[index] >= self.techs[index].cost
+    }
+
+    fn missing(&self, index: usize) -> Vec<String> {
+        self.techs[index].requires.iter()
+            .filter_map(|id| self.techs.iter().position(|tech| &tech.id == id))
+            .filter(|&required| !self.is_done(required))
+            .map(|required| self.techs[required].name.clone())
+            .collect()
+    }
+
+    pub fn state(&self, index: usize) -> TechState {
+        if self.is_done(index) {
+            TechState::Done
+        } else if self.active == Some(index) {
+            TechState::Active
+        } else if self.missing(index).is_empty() {
+            TechState::Available
+        } else {
+            TechState::Locked
+        }
+    }
+
+    // Change de projet; les points déjà investis dans le précédent sont conservés
+    pub fn select(&mut self, index: usize) -> Result<&str, ResearchError> {
+        let tech = self.techs.get(index).ok_or(ResearchError::Unknown(index))?;
+        if self.is_done(index) {
+            return Err(ResearchError::Done(tech.name.clone()));
+        }
+        let missing = self.missing(index);
+        if !missing.is_empty() {
+            return Err(ResearchError::Locked(tech.name.clone(), missing));
+        }
+        self.active = Some(index);
+        Ok(&self.techs[index].name)
+    }
+
+    // Investit les points de la base dans le projet en cours, ou dans le premier disponible si aucun n'a été choisi.
+    // Renvoie le projet terminé, dont l'effet est appliqué à la base
+    pub fn advance(&mut self, base: &mut Base) -> Option<usize> {
+        let index = match self.active {
+            Some(index) => index,
+            None => {
+                let index = (0..self.techs.len()).find(|&index| self.state(index) == TechState::Available)?;
+                self.active = Some(index);
+                index
+            },
+        };
+        let spent = RESEARCH_RATE.min(base.research).min(self.techs[index].cost - self.progress[index]);
+        if spent <= 0 {
+            return None;
+        }
+        base.research -= spent;
+        self.progress[index] += spent;
+        if !self.is_done(index) {
+            return None;
+        }
+        self.techs[index].effect.apply(base);
+        self.active = None;
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::map::generate_map;
+    use crate::simulation::config::BaseConfig;
+
+    fn tree(toml: &str) -> TechTree {
+        toml::from_str(toml).expect("tech tree parses")
+    }
+
+    #[test]
+    fn builtin_tree_locks_its_modules_and_classes() {
+        let tree = TechTree::default();
+        assert!(tree.check().is_ok());
+        assert!(tree.locks_class(RobotClass::Explorer));
+        assert!(!tree.locks_class(RobotClass::Miner) && !tree.locks_class(RobotClass::Scientist));
+
+        let (map, _) = generate_map(1, 20, 20);
+        let mut base = Base::new(&map, &BaseConfig::default());
+        Research::new(tree).lock(&mut base);
+        assert_eq!(base.locked_modules, vec![Module::Tracks, Module::Buoy]);
+        assert_eq!(base.locked_classes, vec![RobotClass::Explorer]);
+    }
+
+    #[test]
+    fn check_rejects_duplicates_costs_and_late_requirements() {
+        let duplicate = tree(r#"
+            [[tech]]
+            id = "a"
+            name = "A"
+            cost = 1
+            effect = { kind = "battery", amount = 1 }
+            [[tech]]
+            id = "a"
+            name = "A again"
+            cost = 1
+            effect = { kind = "battery", amount = 1 }
+        "#);
+        assert!(duplicate.check().is_err());
+
+        let free = tree(r#"
+            [[tech]]
+            id = "a"
+            name = "A"
+            cost = 0
+            effect = { kind = "battery", amount = 1 }
+        "#);
+        assert!(free.check().is_err());
+
+        // Un prérequis défini plus bas, ou un cycle, est refusé
+        let late = tree(r#"
+            [[tech]]
+            id = "a"
+            name = "A"
+            cost = 1
+            requires = ["b"]
+            effect = { kind = "battery", amount = 1 }
+            [[tech]]
+            id = "b"
+            name = "B"
+            cost = 1
+            requires = ["a"]
+            effect = { kind = "battery", amount = 1 }
+        "#);
+        assert!(late.check().is_err());
+    }
+
+    #[test]
+    fn only_locking_techs_withhold_their_unlock_until_done() {
+        let tree = tree(r#"
+            [[tech]]
+            id = "miners"
+            name = "Miners"
+            cost = 1
+            locks = true
+            effect = { kind = "class", class = "miner" }
+            [[tech]]
+            id = "tracks"
+            name = "Tracks"
+            cost = 1
+            effect = { kind = "module", module = "tracks" }
+        "#);
+        assert!(tree.check().is_ok());
+        assert!(tree.locks_class(RobotClass::Miner));
+        assert!(!tree.locks_class(RobotClass::Scientist));
+
+        let (map, _) = generate_map(1, 20, 20);
+        let mut base = Base::new(&map, &BaseConfig { research: 5, ..BaseConfig::default() });
+        let mut research = Research::new(tree);
+        research.lock(&mut base);
+        assert_eq!(base.locked_classes, vec![RobotClass::Miner]);
+        assert!(base.locked_modules.is_empty());
+
+        assert_eq!(research.advance(&mut base), Some(0));
+        assert!(base.locked_classes.is_empty());
+    }
+}
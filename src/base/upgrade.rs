@@ -0,0 +1,110 @@
+use std::{error::Error, fmt};
+use serde::{Deserialize, Serialize};
+use super::base::Base;
+
+// Capacité supplémentaire accordée par un niveau de base
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Capability {
+    Docking { slots: i32 },
+    Charging { rate: i32 },
+    Outposts { count: usize },
+}
+
+impl Capability {
+    fn apply(&self, base: &mut Base) {
+        match *self {
+            Capability::Docking { slots } => base.docking_slots += slots,
+            Capability::Charging { rate } => base.charge_rate += rate,
+            Capability::Outposts { count } => base.max_outposts += count,
+        }
+    }
+}
+
+// Passage au niveau suivant : son coût, les réserves qu'il agrandit et la capacité qu'il débloque
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tier {
+    pub energy: i32,
+    pub iron: i32,
+    #[serde(default)]
+    pub research: i32,
+    #[serde(default)]
+    pub energy_capacity: i32,
+    #[serde(default)]
+    pub iron_storage: i32,
+    #[serde(default)]
+    pub research_storage: i32,
+    #[serde(default)]
+    pub unlock: Option<Capability>,
+}
+
+impl Tier {
+    // Niveaux utilisés quand la configuration n'en définit pas : coûts croissants, la recherche à partir du niveau 3
+    pub fn defaults() -> Vec<Tier> {
+        let tier = |energy, iron, research, unlock| Tier {
+            energy,
+            iron,
+            research,
+            energy_capacity: 250,
+            iron_storage: 50,
+            research_storage: 20,
+            unlock,
+        };
+        vec![
+            tier(100, 10, 0, None),
+            tier(150, 25, 10, Some(Capability::Docking { slots: 1 })),
+            tier(200, 50, 20, Some(Capability::Charging { rate: 5 })),
+            tier(300, 80, 30, Some(Capability::Outposts { count: 1 })),
+        ]
+    }
+
+    // Coût (énergie, fer, recherche) après la réduction obtenue par la recherche
+    pub fn cost(&self, discount: i32) -> (i32, i32, i32) {
+        let discounted = |cost: i32| cost * (100 - discount) / 100;
+        (discounted(self.energy), discounted(self.iron), discounted(self.research))
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        if self.energy < 0 || self.iron < 0 || self.research < 0 {
+            return Err("costs cannot be negative".to_string());
+        }
+        if self.energy_capacity < 0 || self.iron_storage < 0 || self.research_storage < 0 {
+            return Err("storage increases cannot be negative".to_string());
+        }
+        match self.unlock {
+            Some(Capability::Docking { slots }) if slots <= 0 => Err("docking slots must be positive".to_string()),
+            Some(Capability::Charging { rate }) if rate <= 0 => Err("charging rate must be positive".to_string()),
+            Some(Capability::Outposts { count: 0 }) => Err("outpost count must be positive".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, base: &mut Base) {
+        base.energy_capacity += self.energy_capacity;
+        base.iron_storage += self.iron_storage;
+        base.research_storage += self.research_storage;
+        if let Some(capability) = self.unlock {
+            capability.apply(base);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum UpgradeError {
+    MaxLevel(i32),
+    NotEnough { lvl: i32, energy: i32, iron: i32, research: i32 },
+}
+
+impl fmt::Display for UpgradeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UpgradeError::MaxLevel(lvl) => write!(f, "the base is already at its highest level ({})", lvl),
+            UpgradeError::NotEnough { lvl, energy, iron, research } => {
+                write!(f, "level {} costs {} energy, {} iron and {} research", lvl, energy, iron, research)
+            },
+        }
+    }
+}
+
+impl Error for UpgradeError {}
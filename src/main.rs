@@ -1,4 +1,6 @@
-use std::{io, thread, time::{Duration, Instant}, sync::{Arc, Mutex}};
+#![allow(clippy::module_inception)]
+
+use std::{io::{self, Stdout}, thread, time::{Duration, Instant}};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
@@ -7,209 +9,215 @@ use crossterm::{
 use ratatui::{
     backend::CrosstermBackend,
     Terminal,
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Gauge, Paragraph},
     layout::{Layout, Constraint, Direction},
     text::Line,
-    style::{Style, Color},
+    style::{Style, Color, Modifier},
     text::Span,
 };
-use noise::{NoiseFn, Perlin};
-use maps::map::{self, Map, get_biome_from_noise, get_resource_from_biome, Biome, Resource};
-use robots::robot::{self, Robot};
-use base::base::Base;
+use clap::Parser;
+use base::research::TechState;
+use robots::class::RobotClass;
+use robots::robot;
+use simulation::config::{Cli, Config};
+use simulation::replay::{Recorder, Replay};
+use simulation::snapshot::Snapshot;
+use simulation::live::{self, Command, TechView, Update, WorldView};
+use simulation::world::{run_headless, World, TICK};
 
 mod maps;
 mod robots;
 mod base;
+mod simulation;
+
+type Term = Terminal<CrosstermBackend<Stdout>>;
 
 fn main() -> Result<(), io::Error> {
-    // Configuration générale
-    const WIDTH: i32 = 150;
-    const HEIGHT: i32 = 40;
-    const FPS: u64 = 30;
-    const FRAME_DURATION: Duration = Duration::from_millis(1000 / FPS);
-    const NUM_ROBOTS: usize = 3;  // Nombre de robots à créer
-
-    let seed = 155;
-    let perlin = Arc::new(Perlin::new(seed));
-    let (init_map, noise_map) = map::generate_map(seed, WIDTH, HEIGHT);
-    
-    // Partager les ressources entre les threads
-    let map = Arc::new(Mutex::new(init_map));
-    let base = Arc::new(Mutex::new(Base::new(WIDTH, HEIGHT)));
-    
-    // Utiliser Arc<Mutex<Vec<Robot>>> pour partager les robots entre les threads
-    let robots = Arc::new(Mutex::new(Vec::new()));
-    
-    // Créer des robots dans des threads séparés
-    let mut robot_handles = Vec::new();
-    
-    for i in 0..NUM_ROBOTS {
-        let robot_map = Arc::clone(&map);
-        let robot_base = Arc::clone(&base);
-        let robot_perlin = Arc::clone(&perlin);
-        let robots_clone = Arc::clone(&robots);
-        let i_clone = i;
-        
-        let mut robot = Robot::new(WIDTH, HEIGHT, &map.lock().unwrap());
-        if i % 2 == 0 {
-            robot.class = Some("scientist".to_string());
-        } else {
-            robot.class = Some("miner".to_string());
+    // Configuration générale (config.toml + arguments de la ligne de commande)
+    let cli = Cli::parse();
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
         }
-        
-        robot.id = i as i32;
-        
-        robots.lock().unwrap().push(robot.clone());
-        
-        let handle = thread::spawn(move || {
-            let mut robot_thread = robot;
-            let mut previous_time = Instant::now();
-            
-            loop {
-                let current_time = Instant::now();
-                let delta_time = current_time.duration_since(previous_time);
-                previous_time = current_time;
-                
-                let mut map_guard = robot_map.lock().unwrap();
-                let mut base_guard = robot_base.lock().unwrap();
-                
-                let noise_at_robot = robot_perlin.get([robot_thread.x as f64 / 10.0, robot_thread.y as f64 / 10.0, 0.0]);
-                let biome = get_biome_from_noise(noise_at_robot);
-                let resource = get_resource_from_biome(noise_at_robot, biome);
-                
-                if let Some(tile) = map_guard.blueprint
-                    .get_mut(robot_thread.x as usize)
-                    .and_then(|row| row.get_mut(robot_thread.y as usize)) {
-                    tile.biome = biome;
-                    tile.resource = resource;
-                }
-                
-                robot_thread.discover_current_location(biome, resource);
-                robot_thread.update(&mut map_guard, &mut base_guard, delta_time);
-                
-                if let Some(tile) = map_guard.blueprint
-                    .get(robot_thread.x as usize)
-                    .and_then(|row| row.get(robot_thread.y as usize)) {
-                    if tile.resource == Resource::Iron {
-                        robot_thread.iron_collected += 1;
-                        robot_thread.collect_iron(&mut map_guard);
-                    } else if tile.resource == Resource::Research {
-                        robot_thread.research_collected += 1;
-                        robot_thread.collect_research(&mut map_guard);
-                    }
-                }
-                
-                drop(map_guard);
-                drop(base_guard);
-                
-                let mut robots_guard = robots_clone.lock().unwrap();
-                robots_guard[i_clone] = robot_thread.clone();
-                drop(robots_guard);
-                
-                thread::sleep(Duration::from_millis(100));
+    };
+
+    // Les phases parallèles de chaque tick se partagent ce nombre de threads
+    if let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(config.threads).build_global() {
+        eprintln!("Error: cannot start simulation threads: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Some(path) = &cli.replay {
+        return match Replay::load(path) {
+            Ok(replay) => run_replay(replay, &config),
+            Err(e) => {
+                eprintln!("Error: cannot load {}: {}", path.display(), e);
+                std::process::exit(2);
             }
-        });
-        
-        robot_handles.push(handle);
+        };
     }
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let mut world = match &cli.load {
+        Some(path) => match Snapshot::load(path) {
+            Ok(snapshot) => World::from_snapshot(snapshot),
+            Err(e) => {
+                eprintln!("Error: cannot load {}: {}", path.display(), e);
+                std::process::exit(2);
+            }
+        },
+        None => World::new(&config),
+    };
+
+    let mut recorder = match &cli.record {
+        Some(path) => match Recorder::create(path, &world.frame()) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                eprintln!("Error: cannot record to {}: {}", path.display(), e);
+                std::process::exit(2);
+            }
+        },
+        None => None,
+    };
+
+    if cli.headless {
+        if let Err(e) = run_headless(&config, &mut world, recorder.take()) {
+            eprintln!("Error: recording failed: {}", e);
+            std::process::exit(1);
+        }
+        if let Some(path) = &cli.save {
+            if let Err(e) = world.snapshot().save(path) {
+                eprintln!("Error: cannot save {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
+    let frame_duration = Duration::from_millis(1000 / config.fps);
+    let (commands, updates, simulation) = live::spawn(world, recorder);
+    let mut terminal = setup_terminal()?;
+
+    let mut status = String::new();
     let session_start = Instant::now();
-    let mut previous_frame = Instant::now();
+    let mut view = None;
+    // Projet surligné dans le panneau de recherche
+    let mut selected_tech: usize = 0;
 
     loop { // Gameloop
         let frame_start = Instant::now();
-        let delta_time = frame_start.duration_since(previous_frame);
-        previous_frame = frame_start;
 
         if event::poll(Duration::from_millis(1))? {
             if let Event::Key(key) = event::read()? {
-                match key.code {
+                let command = match key.code {
                     KeyCode::Char('q') => break,
-                    KeyCode::Char('u') => {
-                        let mut base_guard = base.lock().unwrap();
-                        base_guard.upgrade_base();
+                    KeyCode::Char('u') => Some(Command::Upgrade),
+                    KeyCode::Char('1') => Some(Command::Order(RobotClass::Scientist)),
+                    KeyCode::Char('2') => Some(Command::Order(RobotClass::Miner)),
+                    KeyCode::Char('3') => Some(Command::Order(RobotClass::Explorer)),
+                    KeyCode::Char('s') => Some(Command::Save(config.snapshot_file.clone())),
+                    KeyCode::Char('l') => Some(Command::Load(config.snapshot_file.clone())),
+                    KeyCode::Up => {
+                        selected_tech = selected_tech.saturating_sub(1);
+                        None
                     },
-                    _ => {}
+                    KeyCode::Down => {
+                        let techs = view.as_ref().map_or(0, |view: &WorldView| view.research.len());
+                        selected_tech = (selected_tech + 1).min(techs.saturating_sub(1));
+                        None
+                    },
+                    KeyCode::Enter => Some(Command::Research(selected_tech)),
+                    _ => None,
+                };
+                if let Some(command) = command {
+                    let _ = commands.send(command);
                 }
             }
         }
 
-        {
-            let mut base_guard = base.lock().unwrap();
-            base_guard.generate_energy();
+        // Seule la dernière image publiée compte : l'affichage ne ralentit jamais la simulation
+        for update in updates.try_iter() {
+            match update {
+                Update::Tick(latest) => view = Some(latest),
+                Update::Status(message) => status = message,
+            }
         }
+        let Some(world) = &view else {
+            thread::sleep(frame_duration);
+            continue;
+        };
 
-        let map_guard = map.lock().unwrap();
-        let base_guard = base.lock().unwrap();
-        let robots_guard = robots.lock().unwrap();
-
-        let mut grid = map_guard.render();
-        grid[base_guard.y as usize][base_guard.x as usize] = Span::styled("B", Style::default().fg(Color::Green));
+        let mut grid = world.map.render();
+        render_docks(&mut grid, (world.base.x, world.base.y), &world.base.outposts);
         
-        for robot in robots_guard.iter() {
-            robot.render(&mut grid);
+        for robot in world.robots.iter() {
+            robot::render_at(&mut grid, robot.x, robot.y);
         }
         
+        let base = &world.base;
+        let next_upgrade = match base.next_upgrade {
+            Some((energy, iron, research)) => format!("next: {} energy, {} iron, {} research", energy, iron, research),
+            None => "max".to_string(),
+        };
         let base_info = format!(
-            "Base Info:\nPosition: ({}, {})\nEnergy: {} / {}\nIron: {}\nResearch: {}\nBase lvl: {}\nAppuyez sur 'u' pour améliorer la base.",
-            base_guard.x, base_guard.y, base_guard.energy, base_guard.energy_capacity, 
-            base_guard.iron, base_guard.research, base_guard.lvl
+            "Base Info:\nPosition: ({}, {})\nEnergy: {} / {}\nIron: {} / {}\nResearch: {} / {}\nBase lvl: {} ({})\nOutposts: {} / {} | Docking slots: {} | Charge: {}/action\nAppuyez sur 'u' pour améliorer la base.\n's' : sauvegarder, 'l' : charger.",
+            base.x, base.y, base.energy, base.energy_capacity, 
+            base.iron, base.iron_storage, base.research, base.research_storage, base.lvl, next_upgrade,
+            base.outposts.len(), base.max_outposts, base.docking_slots, base.charge_rate
         );
+        let storage = storage_gauges((base.iron, base.iron_storage), (base.research, base.research_storage));
         
         let session_time = session_start.elapsed();
-        let debug_info = format!("Session Time: {:.2?} | Robots: {}", session_time, robots_guard.len());
+        let debug_info = format!("Session Time: {:.2?} | Tick: {} | Robots: {} | Seed: {}\n{}", session_time, world.tick, world.robots.len(), world.map.seed, status);
         
-        let robot_info = if !robots_guard.is_empty() {
-            let robot = &robots_guard[0];
+        let robot_info = if let Some(robot) = world.robots.first() {
             format!(
-                "Robot Info:\nPosition: ({}, {})\nEnergy: {}\nIron: {}\nResearch: {}\nClass: {}\n{}",
+                "Robot Info:\nPosition: ({}, {})\nEnergy: {}\nIron: {}\nResearch: {}\nCargo: {} / {}\nClass: {}\n{}",
                 robot.x, robot.y, robot.energy, robot.iron_collected, robot.research_collected, 
-                robot.class.as_deref().unwrap_or("None"), debug_info
+                robot.cargo, robot.cargo_capacity, robot.class, debug_info
             )
         } else {
             format!("No robots available\n{}", debug_info)
         };
         
         let lines: Vec<Line> = grid.iter().map(|row| Line::from(row.clone())).collect();
-        
-        drop(base_guard);
-        drop(robots_guard);
-
-        terminal.draw(|f| {
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
-                .split(f.size());
-            let map_widget = Paragraph::new(lines)
-                .block(Block::default().borders(Borders::ALL).title("Map"));
-            f.render_widget(map_widget, chunks[0]);
-        
-            let info_chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-                .split(chunks[1]);
-            let robot_info_widget = Paragraph::new(robot_info)
-                .block(Block::default().borders(Borders::ALL).title("Robots Info"));
-            f.render_widget(robot_info_widget, info_chunks[0]);
-        
-            let base_info_widget = Paragraph::new(base_info)
-                .block(Block::default().borders(Borders::ALL).title("Base Info"));
-            f.render_widget(base_info_widget, info_chunks[1]);
-        })?;
+
+        let factory = &world.factory;
+        let mut factory_info = format!(
+            "1/2/3 : scientist, miner, explorer\n{} iron, {} energy each ({} / {})",
+            factory.iron, factory.energy, factory.queue.len(), factory.max_queue
+        );
+        for (class, ticks) in &factory.queue {
+            factory_info.push_str(&format!("\n{} in {:.1?}", class, TICK * *ticks));
+        }
+
+        let research_lines = research_lines(&world.research, selected_tech);
+
+        draw_panels(&mut terminal, lines, robot_info, base_info, &storage, Some(factory_info), Some(research_lines))?;
 
         let elapsed = frame_start.elapsed();
-        if elapsed < FRAME_DURATION {
-            thread::sleep(FRAME_DURATION - elapsed); 
+        if elapsed < frame_duration {
+            thread::sleep(frame_duration - elapsed); 
         }
     }
 
+    // La simulation termine l'enregistrement en cours avant de s'arrêter
+    let _ = commands.send(Command::Quit);
+    let recorded = simulation.join().unwrap_or(Ok(()));
+    restore_terminal(&mut terminal)?;
+    recorded
+}
+
+fn setup_terminal() -> Result<Term, io::Error> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    Terminal::new(backend)
+}
+
+fn restore_terminal(terminal: &mut Term) -> Result<(), io::Error> {
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -218,4 +226,181 @@ fn main() -> Result<(), io::Error> {
     )?;
     terminal.show_cursor()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+// La base en B, ses avant-postes en O
+fn render_docks(grid: &mut [Vec<Span>], (x, y): (i32, i32), outposts: &[(i32, i32)]) {
+    grid[y as usize][x as usize] = Span::styled("B", Style::default().fg(Color::Green));
+    for &(x, y) in outposts {
+        grid[y as usize][x as usize] = Span::styled("O", Style::default().fg(Color::Green));
+    }
+}
+
+// Remplissage des réserves de la base : (nom, quantité, capacité)
+fn storage_gauges((iron, iron_storage): (i32, i32), (research, research_storage): (i32, i32)) -> [(&'static str, i32, i32); 2] {
+    [
+        ("Iron", iron, iron_storage),
+        ("Research", research, research_storage),
+    ]
+}
+
+// Un projet par ligne avec son avancement, le projet sous le curseur en inversé
+fn research_lines(techs: &[TechView], selected: usize) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::from("Up/Down, Enter : choisir")];
+    for (index, tech) in techs.iter().enumerate() {
+        let (mark, color) = match tech.state {
+            TechState::Done => ("✓", Color::Green),
+            TechState::Active => ("▶", Color::Yellow),
+            TechState::Available => (" ", Color::White),
+            TechState::Locked => ("✗", Color::DarkGray),
+        };
+        let mut style = Style::default().fg(color);
+        if index == selected {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        lines.push(Line::styled(format!("{} {} {}/{}", mark, tech.name, tech.progress, tech.cost), style));
+    }
+    lines
+}
+
+fn draw_panels(terminal: &mut Term, lines: Vec<Line>, robot_info: String, base_info: String, storage: &[(&str, i32, i32)], factory_info: Option<String>, research: Option<Vec<Line>>) -> Result<(), io::Error> {
+    terminal.draw(|f| {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
+            .split(f.area());
+        // Le panneau de recherche se place à droite de la carte, sauf en relecture
+        let map_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(32)].as_ref())
+            .split(chunks[0]);
+        let map_area = if research.is_some() { map_chunks[0] } else { chunks[0] };
+        if let Some(research) = research {
+            let research_widget = Paragraph::new(research)
+                .block(Block::default().borders(Borders::ALL).title("Research"));
+            f.render_widget(research_widget, map_chunks[1]);
+        }
+        let map_widget = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title("Map"));
+        f.render_widget(map_widget, map_area);
+    
+        let info_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(35), Constraint::Percentage(25)].as_ref())
+            .split(chunks[1]);
+        let robot_info_widget = Paragraph::new(robot_info)
+            .block(Block::default().borders(Borders::ALL).title("Robots Info"));
+        f.render_widget(robot_info_widget, info_chunks[0]);
+    
+        let base_info_widget = Paragraph::new(base_info)
+            .block(Block::default().borders(Borders::ALL).title("Base Info"));
+        f.render_widget(base_info_widget, info_chunks[1]);
+
+        // La file de l'usine s'affiche sous les réserves, sauf en relecture
+        let side_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(storage.len() as u16 + 2), Constraint::Min(0)].as_ref())
+            .split(info_chunks[2]);
+        let storage_area = if factory_info.is_some() { side_chunks[0] } else { info_chunks[2] };
+        if let Some(factory_info) = factory_info {
+            let factory_widget = Paragraph::new(factory_info)
+                .block(Block::default().borders(Borders::ALL).title("Factory"));
+            f.render_widget(factory_widget, side_chunks[1]);
+        }
+
+        let storage_block = Block::default().borders(Borders::ALL).title("Storage");
+        let gauge_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(1); storage.len()])
+            .split(storage_block.inner(storage_area));
+        f.render_widget(storage_block, storage_area);
+        for (&(name, amount, capacity), area) in storage.iter().zip(gauge_chunks.iter()) {
+            let ratio = if capacity > 0 { (amount as f64 / capacity as f64).clamp(0.0, 1.0) } else { 1.0 };
+            // Rouge quand la réserve est pleine : il est temps d'améliorer la base
+            let color = if ratio >= 1.0 { Color::Red } else if ratio >= 0.8 { Color::Yellow } else { Color::Green };
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .label(format!("{}: {} / {}", name, amount, capacity))
+                .ratio(ratio);
+            f.render_widget(gauge, *area);
+        }
+    })?;
+    Ok(())
+}
+
+fn run_replay(mut replay: Replay, config: &Config) -> Result<(), io::Error> {
+    let frame_duration = Duration::from_millis(1000 / config.fps);
+    let mut terminal = setup_terminal()?;
+
+    let mut paused = false;
+    let mut speed: u64 = 1;
+    let mut tick_clock = Duration::ZERO;
+    let mut previous_frame = Instant::now();
+
+    loop {
+        let frame_start = Instant::now();
+        let delta_time = frame_start.duration_since(previous_frame);
+        previous_frame = frame_start;
+
+        if event::poll(Duration::from_millis(1))? {
+            if let Event::Key(key) = event::read()? {
+                let tick = replay.state.tick;
+                match key.code {
+                    KeyCode::Char('q') => break,
+                    KeyCode::Char(' ') => paused = !paused,
+                    KeyCode::Char('+') => speed = (speed * 2).min(64),
+                    KeyCode::Char('-') => speed = (speed / 2).max(1),
+                    KeyCode::Right => replay.seek(tick + 10),
+                    KeyCode::Left => replay.seek(tick.saturating_sub(10)),
+                    KeyCode::PageDown => replay.seek(tick + 600),
+                    KeyCode::PageUp => replay.seek(tick.saturating_sub(600)),
+                    KeyCode::Home => replay.seek(replay.first_tick()),
+                    KeyCode::End => replay.seek(replay.last_tick),
+                    _ => {}
+                }
+            }
+        }
+
+        // Un tick du journal par TICK de temps réel, multiplié par la vitesse
+        if !paused && replay.state.tick < replay.last_tick {
+            tick_clock += delta_time * speed as u32;
+            let ticks = (tick_clock.as_millis() / TICK.as_millis()) as u64;
+            if ticks > 0 {
+                tick_clock -= TICK * ticks as u32;
+                replay.seek(replay.state.tick + ticks);
+            }
+        }
+
+        let frame = &replay.state;
+        let mut grid = frame.map.render();
+        let outposts: Vec<(i32, i32)> = frame.base.outposts.iter().map(|outpost| (outpost.x, outpost.y)).collect();
+        render_docks(&mut grid, (frame.base.x, frame.base.y), &outposts);
+        for robot in &frame.robots {
+            robot::render_at(&mut grid, robot.x, robot.y);
+        }
+        let lines: Vec<Line> = grid.iter().map(|row| Line::from(row.clone())).collect();
+
+        let base_info = format!(
+            "Replay: tick {} / {} ({}x{})\nIron: {}\nResearch: {}\nBase lvl: {}\nEspace : pause, +/- : vitesse\n←/→ : ±10 ticks, PgUp/PgDn : ±600, Début/Fin",
+            frame.tick, replay.last_tick, speed, if paused { ", paused" } else { "" },
+            frame.base.iron, frame.base.research, frame.base.lvl
+        );
+        let robot_info = match frame.robots.first() {
+            Some(robot) => format!(
+                "Robot Info:\nPosition: ({}, {})\nEnergy: {}\nIron: {}\nResearch: {}\nClass: {}\nRobots: {} | Seed: {}",
+                robot.x, robot.y, robot.energy, robot.iron_collected, robot.research_collected,
+                robot.class, frame.robots.len(), frame.map.seed
+            ),
+            None => "No robots available".to_string(),
+        };
+
+        draw_panels(&mut terminal, lines, robot_info, base_info, &storage_gauges((frame.base.iron, frame.base.iron_storage), (frame.base.research, frame.base.research_storage)), None, None)?;
+
+        let elapsed = frame_start.elapsed();
+        if elapsed < frame_duration {
+            thread::sleep(frame_duration - elapsed);
+        }
+    }
+
+    restore_terminal(&mut terminal)
+}
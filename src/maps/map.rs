@@ -1,30 +1,84 @@
+use clap::ValueEnum;
 use noise::{NoiseFn, Perlin};
 use ratatui::text::Span;
-use ratatui::style::{Style, Color};
+use ratatui::style::{Style, Color, Modifier};
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Biome {
     Plain,
     Desert,
     Forest,
     Mountain,
     Water,
+    Unknown,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Resource {
     None,
     Iron,
     Research,
 }
 
-#[derive(Clone)]
+// Voisinage utilisé pour les déplacements : cases adjacentes seulement, ou diagonales en plus
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Topology {
+    #[default]
+    Four,
+    Eight,
+}
+
+impl Topology {
+    pub fn deltas(self) -> &'static [(i32, i32)] {
+        match self {
+            Topology::Four => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+            Topology::Eight => &[(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)],
+        }
+    }
+
+    // Une diagonale coûte une fois et demie le pas droit, arrondi au supérieur pour rester entier
+    pub fn step_cost(cost: u32, (dx, dy): (i32, i32)) -> u32 {
+        if dx != 0 && dy != 0 { (cost * 3).div_ceil(2) } else { cost }
+    }
+
+    // Nombre minimal de cases à parcourir
+    pub fn steps(self, (x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> u32 {
+        let (dx, dy) = (x1.abs_diff(x2), y1.abs_diff(y2));
+        match self {
+            Topology::Four => dx + dy,
+            Topology::Eight => dx.max(dy),
+        }
+    }
+
+    // Heuristique A* pour un coût minimal de 1 par case : Manhattan en 4 directions, octile en 8
+    pub fn distance(self, (x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> u32 {
+        let (dx, dy) = (x1.abs_diff(x2), y1.abs_diff(y2));
+        match self {
+            Topology::Four => dx + dy,
+            Topology::Eight => dx.max(dy) + dx.min(dy) / 2,
+        }
+    }
+}
+
+// Taille d'un gisement par point de richesse, et seuils d'affichage riche / presque épuisé
+const DEPOSIT_SIZE: i32 = 6;
+const RICH_DEPOSIT: i32 = 12;
+const LOW_DEPOSIT: i32 = 4;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TileInfo {
     pub biome: Biome,
     pub resource: Resource,
+    // Unités restantes dans le gisement, et unités extraites à chaque passe de minage
+    pub quantity: i32,
+    pub richness: i32,
+    // Tick de la dernière observation, pour ne garder que l'information la plus récente
+    pub seen: u64,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
     pub seed: u32,
     pub width: i32,
@@ -33,24 +87,20 @@ pub struct Map {
 }
 
 impl Map {
-    pub fn render(&self) -> Vec<Vec<Span>> {
+    pub fn render(&self) -> Vec<Vec<Span<'_>>> {
         let width_usize = self.width as usize;
         let height_usize = self.height as usize;
         let mut grid = vec![vec![Span::raw(" "); width_usize]; height_usize];
     
-        for y in 0..height_usize {
-            for x in 0..width_usize {
-                let tile = &self.blueprint[x][y];
+        for (x, column) in self.blueprint.iter().enumerate().take(width_usize) {
+            for (y, tile) in column.iter().enumerate().take(height_usize) {
                 let span = match (tile.resource, tile.biome) {
-                    (Resource::Iron, _) => {
-                        Span::styled("F", Style::default().fg(Color::Yellow))
-                    },
-                    (Resource::Research, _) => {
-                        Span::styled("T", Style::default().fg(Color::Cyan))
-                    },
+                    (Resource::Iron, _) => deposit_span(tile, "F", "f", Color::Yellow),
+                    (Resource::Research, _) => deposit_span(tile, "T", "t", Color::Cyan),
                     (_, Biome::Mountain) => {
                         Span::styled("#", Style::default().fg(Color::DarkGray))
                     },
+                    (_, Biome::Unknown) => Span::raw(" "),
                     _ => Span::raw("."),
                 };
                 grid[y][x] = span;
@@ -60,19 +110,71 @@ impl Map {
         grid
     }
 
-    pub fn discover_area(&mut self, x: usize, y: usize, radius: usize, biome: Biome, resource: Resource) {
+    pub fn unknown(seed: u32, width: i32, height: i32) -> Self {
+        Self {
+            seed,
+            width,
+            height,
+            blueprint: vec![
+                vec![TileInfo { biome: Biome::Unknown, resource: Resource::None, quantity: 0, richness: 0, seen: 0 }; height as usize];
+                width as usize
+            ],
+        }
+    }
+
+    // Copie dans cette carte les cases réelles situées dans le rayon donné
+    pub fn discover_area(&mut self, source: &Map, x: usize, y: usize, radius: usize, tick: u64) {
         let width = self.blueprint.len();
         let height = if width > 0 { self.blueprint[0].len() } else { 0 };
-        let start_x = if x >= radius { x - radius } else { 0 };
-        let start_y = if y >= radius { y - radius } else { 0 };
+        let start_x = x.saturating_sub(radius);
+        let start_y = y.saturating_sub(radius);
         let end_x = usize::min(x + radius, width - 1);
         let end_y = usize::min(y + radius, height - 1);
         for i in start_x..=end_x {
             for j in start_y..=end_y {
-                self.blueprint[i][j] = TileInfo { biome, resource };
+                self.blueprint[i][j] = TileInfo { seen: tick, ..source.blueprint[i][j].clone() };
             }
         }
     }
+
+    pub fn count_unknown(&self, x: usize, y: usize, radius: usize) -> usize {
+        let end_x = usize::min(x + radius, self.blueprint.len() - 1);
+        let end_y = usize::min(y + radius, self.blueprint[0].len() - 1);
+        self.blueprint[x.saturating_sub(radius)..=end_x]
+            .iter()
+            .map(|column| column[y.saturating_sub(radius)..=end_y].iter().filter(|tile| tile.biome == Biome::Unknown).count())
+            .sum()
+    }
+
+    // Retire jusqu'à `limit` unités du gisement de la case, qui disparaît une fois vide
+    pub fn extract(&mut self, x: usize, y: usize, limit: i32) -> i32 {
+        let tile = &mut self.blueprint[x][y];
+        if tile.resource == Resource::None {
+            return 0;
+        }
+        let amount = tile.richness.min(tile.quantity).min(limit).max(0);
+        tile.quantity -= amount;
+        if tile.quantity <= 0 {
+            tile.resource = Resource::None;
+            tile.quantity = 0;
+            tile.richness = 0;
+        }
+        amount
+    }
+
+    // Renvoie vrai si des cases jusque-là inconnues ont été révélées
+    pub fn merge(&mut self, other: &Map) -> bool {
+        let mut revealed = false;
+        for (column, other_column) in self.blueprint.iter_mut().zip(&other.blueprint) {
+            for (tile, other_tile) in column.iter_mut().zip(other_column) {
+                if other_tile.biome != Biome::Unknown && (tile.biome == Biome::Unknown || other_tile.seen > tile.seen) {
+                    revealed |= tile.biome == Biome::Unknown;
+                    *tile = other_tile.clone();
+                }
+            }
+        }
+        revealed
+    }
 }
 
 pub fn generate_map(seed: u32, width: i32, height: i32) -> (Map, Vec<Vec<f64>>) {
@@ -80,7 +182,7 @@ pub fn generate_map(seed: u32, width: i32, height: i32) -> (Map, Vec<Vec<f64>>)
     let width_usize = width as usize;
     let height_usize = height as usize;
     let mut blueprint: Vec<Vec<TileInfo>> = vec![
-        vec![TileInfo { biome: Biome::Plain, resource: Resource::None }; height_usize];
+        vec![TileInfo { biome: Biome::Plain, resource: Resource::None, quantity: 0, richness: 0, seen: 0 }; height_usize];
         width_usize
     ];
     let mut noise_map: Vec<Vec<f64>> = vec![vec![0.0; height_usize]; width_usize];
@@ -93,7 +195,12 @@ pub fn generate_map(seed: u32, width: i32, height: i32) -> (Map, Vec<Vec<f64>>)
             noise_map[x][y] = noise;
             let biome = get_biome_from_noise(noise);
             let resource = get_resource_from_biome(noise, biome);
-            blueprint[x][y] = TileInfo { biome, resource };
+            let richness = if resource == Resource::None {
+                0
+            } else {
+                get_richness_from_noise(perlin.get([nx * 10.0, ny * 10.0, 1.0]))
+            };
+            blueprint[x][y] = TileInfo { biome, resource, quantity: richness * DEPOSIT_SIZE, richness, seen: 0 };
         }
     }
 
@@ -129,4 +236,76 @@ pub fn get_resource_from_biome(noise: f64, _biome: Biome) -> Resource {
     } else {
         Resource::None
     }
-}
\ No newline at end of file
+}
+
+// Richesse de 1 à 3 tirée d'un second échantillon de bruit, pour varier les gisements d'un même biome
+pub fn get_richness_from_noise(noise: f64) -> i32 {
+    if noise < -0.2 {
+        1
+    } else if noise < 0.2 {
+        2
+    } else {
+        3
+    }
+}
+
+// Les gisements riches sont en gras, ceux presque épuisés en minuscule et atténués
+fn deposit_span(tile: &TileInfo, symbol: &'static str, depleted: &'static str, color: Color) -> Span<'static> {
+    let style = Style::default().fg(color);
+    if tile.quantity >= RICH_DEPOSIT {
+        Span::styled(symbol, style.add_modifier(Modifier::BOLD))
+    } else if tile.quantity <= LOW_DEPOSIT {
+        Span::styled(depleted, style.add_modifier(Modifier::DIM))
+    } else {
+        Span::styled(symbol, style)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(biome: Biome, quantity: i32, seen: u64) -> TileInfo {
+        TileInfo { biome, resource: Resource::Iron, quantity, richness: 1, seen }
+    }
+
+    #[test]
+    fn merge_keeps_the_most_recent_observation() {
+        let mut base = Map::unknown(0, 3, 1);
+        base.blueprint[0][0] = tile(Biome::Plain, 10, 5);
+        base.blueprint[1][0] = tile(Biome::Plain, 10, 5);
+        let mut robot = Map::unknown(0, 3, 1);
+        robot.blueprint[0][0] = tile(Biome::Plain, 4, 8);
+        robot.blueprint[1][0] = tile(Biome::Plain, 2, 3);
+
+        assert!(!base.merge(&robot));
+        assert_eq!(base.blueprint[0][0].quantity, 4);
+        assert_eq!(base.blueprint[1][0].quantity, 10);
+        assert!(base.blueprint[2][0].biome == Biome::Unknown);
+    }
+
+    #[test]
+    fn merge_reports_revealed_tiles_and_never_forgets_known_ones() {
+        let mut base = Map::unknown(0, 2, 1);
+        base.blueprint[0][0] = tile(Biome::Forest, 3, 1);
+        let mut robot = Map::unknown(0, 2, 1);
+        robot.blueprint[1][0] = tile(Biome::Desert, 6, 0);
+
+        assert!(base.merge(&robot));
+        assert!(base.blueprint[0][0].biome == Biome::Forest);
+        assert!(base.blueprint[1][0].biome == Biome::Desert);
+    }
+
+    #[test]
+    fn heuristics_never_overestimate_the_cheapest_move() {
+        for (dx, dy) in [(0i32, 0i32), (3, 0), (0, 5), (4, 4), (7, 2), (1, 6)] {
+            let (straight, diagonal) = (dx.abs_diff(dy), dx.min(dy) as u32);
+            // Pas droits et diagonales au coût minimal de 1 par case
+            let cheapest = straight + diagonal * Topology::step_cost(1, (1, 1));
+            assert!(Topology::Eight.distance((0, 0), (dx, dy)) <= cheapest);
+            assert_eq!(Topology::Four.distance((0, 0), (dx, dy)), (dx + dy) as u32);
+            assert_eq!(Topology::Eight.steps((0, 0), (dx, dy)), dx.max(dy) as u32);
+        }
+        assert_eq!(Topology::Eight.distance((0, 0), (4, 4)), 6);
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::maps::map::Resource;
+use super::robot::{Robot, REPORT_THRESHOLD};
+
+// Comportement propre à une classe : ajouter une classe revient à ajouter une implémentation
+pub trait ClassBehaviour {
+    fn name(&self) -> &'static str;
+    // Ressource collectée, aucune pour une classe qui ne collecte rien
+    fn target_resource(&self) -> Option<Resource>;
+    fn cargo_capacity(&self) -> i32;
+
+    // Nombre de cases inconnues de la base à partir duquel le robot rentre les signaler
+    fn report_threshold(&self) -> Option<usize> {
+        None
+    }
+
+    fn explore(&self, robot: &Robot) -> Option<(Vec<(i32, i32)>, u32)> {
+        robot.path_to_unknown()
+    }
+}
+
+pub struct Scientist;
+pub struct Miner;
+pub struct Explorer;
+
+impl ClassBehaviour for Scientist {
+    fn name(&self) -> &'static str {
+        "scientist"
+    }
+
+    fn target_resource(&self) -> Option<Resource> {
+        Some(Resource::Research)
+    }
+
+    fn cargo_capacity(&self) -> i32 {
+        6
+    }
+}
+
+impl ClassBehaviour for Miner {
+    fn name(&self) -> &'static str {
+        "miner"
+    }
+
+    fn target_resource(&self) -> Option<Resource> {
+        Some(Resource::Iron)
+    }
+
+    fn cargo_capacity(&self) -> i32 {
+        10
+    }
+}
+
+impl ClassBehaviour for Explorer {
+    fn name(&self) -> &'static str {
+        "explorer"
+    }
+
+    fn target_resource(&self) -> Option<Resource> {
+        None
+    }
+
+    fn cargo_capacity(&self) -> i32 {
+        0
+    }
+
+    fn report_threshold(&self) -> Option<usize> {
+        Some(REPORT_THRESHOLD)
+    }
+
+    fn explore(&self, robot: &Robot) -> Option<(Vec<(i32, i32)>, u32)> {
+        robot.path_to_frontier()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RobotClass {
+    Scientist,
+    Miner,
+    Explorer,
+}
+
+impl RobotClass {
+    pub fn behaviour(self) -> &'static dyn ClassBehaviour {
+        match self {
+            RobotClass::Scientist => &Scientist,
+            RobotClass::Miner => &Miner,
+            RobotClass::Explorer => &Explorer,
+        }
+    }
+}
+
+impl fmt::Display for RobotClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.behaviour().name())
+    }
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+// Dépenses d'énergie d'un robot hors déplacement, et poids de sa cargaison. Le coût d'entrée sur une case
+// dépend déjà du biome et de l'équipement (voir ModuleBehaviour::energy_cost)
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnergyCosts {
+    // Unités de cargaison qui ajoutent 1 au coût de chaque case parcourue (0 : la cargaison ne pèse rien)
+    pub cargo_weight: i32,
+    // Par passe d'extraction
+    pub mine: i32,
+    // Par relevé des capteurs qui révèle des cases inconnues du robot
+    pub scan: i32,
+    // Par action passée à attendre loin de tout point d'amarrage, sans miner
+    pub idle: i32,
+}
+
+impl Default for EnergyCosts {
+    fn default() -> Self {
+        Self {
+            cargo_weight: 5,
+            mine: 1,
+            scan: 1,
+            idle: 1,
+        }
+    }
+}
+
+impl EnergyCosts {
+    // Surcoût de chaque case parcourue avec cette cargaison
+    pub fn load(&self, cargo: i32) -> u32 {
+        if self.cargo_weight > 0 { (cargo / self.cargo_weight) as u32 } else { 0 }
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        if self.cargo_weight < 0 || self.mine < 0 || self.scan < 0 || self.idle < 0 {
+            return Err("energy costs cannot be negative".to_string());
+        }
+        Ok(())
+    }
+}
@@ -1 +1,6 @@
-pub mod robot;
\ No newline at end of file
+pub mod class;
+pub mod energy;
+pub mod module;
+pub mod robot;
+pub mod strategy;
+pub mod traffic;
\ No newline at end of file
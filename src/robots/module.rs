@@ -0,0 +1,135 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::maps::map::Biome;
+
+// Caractéristiques d'un équipement : ajouter un module revient à ajouter une implémentation
+pub trait ModuleBehaviour {
+    fn name(&self) -> &'static str;
+    // Énergie dépensée pour entrer sur une case de ce biome, None si le biome est infranchissable
+    fn energy_cost(&self, biome: Biome) -> Option<u32>;
+    // Nombre de cases parcourues par déplacement en partant de ce biome
+    fn speed(&self, biome: Biome) -> usize;
+    // Place ajoutée à la soute de la classe
+    fn cargo_bonus(&self) -> i32;
+}
+
+pub struct Bare;
+pub struct Wheels;
+pub struct Tracks;
+pub struct Buoy;
+
+impl ModuleBehaviour for Bare {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn energy_cost(&self, biome: Biome) -> Option<u32> {
+        match biome {
+            Biome::Plain | Biome::Unknown => Some(2),
+            Biome::Desert | Biome::Forest => Some(3),
+            Biome::Mountain | Biome::Water => None,
+        }
+    }
+
+    fn speed(&self, _biome: Biome) -> usize {
+        1
+    }
+
+    fn cargo_bonus(&self) -> i32 {
+        0
+    }
+}
+
+// Rapides sur terrain dégagé, mais ralenties et gourmandes en forêt
+impl ModuleBehaviour for Wheels {
+    fn name(&self) -> &'static str {
+        "wheels"
+    }
+
+    fn energy_cost(&self, biome: Biome) -> Option<u32> {
+        match biome {
+            Biome::Plain | Biome::Unknown => Some(2),
+            Biome::Desert => Some(3),
+            Biome::Forest => Some(4),
+            Biome::Mountain | Biome::Water => None,
+        }
+    }
+
+    fn speed(&self, biome: Biome) -> usize {
+        if biome == Biome::Forest { 1 } else { 2 }
+    }
+
+    fn cargo_bonus(&self) -> i32 {
+        2
+    }
+}
+
+// Lentes mais économes : la forêt et même la montagne ne leur coûtent pas plus que la plaine,
+// moins que la plaine aux autres équipements. Le sable les freine comme tout le monde
+impl ModuleBehaviour for Tracks {
+    fn name(&self) -> &'static str {
+        "tracks"
+    }
+
+    fn energy_cost(&self, biome: Biome) -> Option<u32> {
+        match biome {
+            Biome::Plain | Biome::Forest | Biome::Mountain | Biome::Unknown => Some(1),
+            Biome::Desert => Some(2),
+            Biome::Water => None,
+        }
+    }
+
+    fn speed(&self, _biome: Biome) -> usize {
+        1
+    }
+
+    fn cargo_bonus(&self) -> i32 {
+        5
+    }
+}
+
+impl ModuleBehaviour for Buoy {
+    fn name(&self) -> &'static str {
+        "buoy"
+    }
+
+    fn energy_cost(&self, biome: Biome) -> Option<u32> {
+        match biome {
+            Biome::Plain | Biome::Water | Biome::Unknown => Some(2),
+            Biome::Desert | Biome::Forest => Some(3),
+            Biome::Mountain => None,
+        }
+    }
+
+    fn speed(&self, _biome: Biome) -> usize {
+        1
+    }
+
+    fn cargo_bonus(&self) -> i32 {
+        0
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Module {
+    Wheels,
+    Tracks,
+    Buoy,
+}
+
+impl Module {
+    pub fn behaviour(self) -> &'static dyn ModuleBehaviour {
+        match self {
+            Module::Wheels => &Wheels,
+            Module::Tracks => &Tracks,
+            Module::Buoy => &Buoy,
+        }
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.behaviour().name())
+    }
+}
@@ -1,234 +1,662 @@
-use pathfinding::prelude::astar;
-use crate::maps::map::{Map, Resource, Biome, TileInfo};
+use pathfinding::prelude::{astar, build_path, dijkstra, dijkstra_all};
+use crate::maps::map::{Map, Resource, Biome, TileInfo, Topology};
 use crate::base::base::Base;
+use crate::base::field::ReturnField;
+use super::class::RobotClass;
+use super::energy::EnergyCosts;
+use super::module::{Bare, Module, ModuleBehaviour};
+use super::strategy::{Action, StrategyKind, View, FULL_CHARGE, RETURN_MARGIN};
+use super::traffic::{self, Priority, WINDOW};
 use ratatui::style::{Style, Color, Modifier};
 use ratatui::text::Span;
+use serde::{Deserialize, Serialize};
 use std::time::{Duration};
 
-#[derive(Clone)]
+// Rayon connu autour de la base au démarrage, puis rayon de vision du robot
+pub const START_VISION: usize = 5;
+pub const SENSOR_RADIUS: usize = 2;
+// Cases découvertes mais encore inconnues de la base au-delà desquelles un explorateur rentre les signaler
+pub const REPORT_THRESHOLD: usize = 150;
+pub const BATTERY_CAPACITY: i32 = 100;
+// Ticks passés sur un gisement pour en extraire une passe
+pub const MINING_TICKS: u32 = 20;
+// Un déplacement par seconde, soit tous les 10 ticks de 100 ms : c'est le créneau des réservations
+pub const ACTION_TICKS: u64 = 10;
+// Déplacements bloqués d'affilée avant de s'écarter pour laisser passer, puis avant d'abandonner son chemin
+// pour en replanifier un, par exemple vers un autre point d'amarrage (typiquement dans un couloir d'une case de large)
+const BLOCKED_LIMIT: u32 = 3;
+const DEADLOCK_LIMIT: u32 = 10;
+const BIOMES: [Biome; 5] = [Biome::Plain, Biome::Desert, Biome::Forest, Biome::Mountain, Biome::Water];
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Robot {
     pub id: i32,
     pub x: i32,
     pub y: i32,
-    map_width: i32,
-    map_height: i32,
     pub energy: i32,
+    // Capacité de la batterie et rayon de vision, relevés à quai par la recherche
+    pub battery: i32,
+    pub sensor_radius: usize,
     pub iron_collected: i32,
     pub research_collected: i32,
     pub known_map: Map,
     pub path: Option<Vec<(i32, i32)>>,
     pub move_cooldown: f32,
-    pub modules: Option<String>,
+    pub mining: u32,
+    pub modules: Option<Module>,
     pub modified: bool,
-    pub class: Option<String>,
+    pub class: RobotClass,
+    pub strategy: StrategyKind,
+    pub topology: Topology,
+    // Une seule case par robot, avec réservation des déplacements à venir
+    pub avoid_collisions: bool,
+    pub blocked: u32,
+    pub energy_costs: EnergyCosts,
+    // Champ de retour de la base prolongé sur la carte du robot quand il est au-delà de ce que la base connaît,
+    // avec la version des champs de la base à partir de laquelle il a été calculé
+    #[serde(skip)]
+    home_field: Option<(u64, ReturnField)>,
 }
 
 impl Robot {
-    pub fn new(map_width: i32, map_height: i32, full_map: &Map) -> Self {
+    pub fn new(map_width: i32, map_height: i32, full_map: &Map, class: RobotClass) -> Self {
+        let x = map_width / 2;
+        let y = map_height / 2;
+        let mut known_map = Map::unknown(full_map.seed, full_map.width, full_map.height);
+        known_map.discover_area(full_map, x as usize, y as usize, START_VISION, 0);
         Self {
             id: 0,
-            x: map_width / 2,
-            y: map_height / 2,
-            map_width,
-            map_height,
-            energy: 100,
+            x,
+            y,
+            energy: BATTERY_CAPACITY,
+            battery: BATTERY_CAPACITY,
+            sensor_radius: SENSOR_RADIUS,
             iron_collected: 0,
             research_collected: 0,
-            known_map: full_map.clone(),
+            known_map,
             path: None,
             move_cooldown: 0.0,
+            mining: 0,
             modules: None,
             modified: false,
-            class: None,
+            class,
+            strategy: StrategyKind::Greedy,
+            topology: Topology::Four,
+            avoid_collisions: false,
+            blocked: 0,
+            energy_costs: EnergyCosts::default(),
+            home_field: None,
         }
     }
 
-    pub fn collect_iron(&mut self, map: &mut Map) {           
-            self.known_map.blueprint[self.x as usize][self.y as usize].resource = Resource::None;
-            map.blueprint[self.x as usize][self.y as usize].resource = Resource::None;
-    }
+    // Avance l'extraction sur la case courante; une passe ne rapporte qu'après MINING_TICKS ticks sur place,
+    // et coûte son énergie à la fin
+    pub fn mine(&mut self, map: &mut Map, tick: u64) -> Option<(Resource, i32)> {
+        let (x, y) = (self.x as usize, self.y as usize);
+        let resource = map.blueprint[x][y].resource;
+        if resource == Resource::None || !self.has_room() || self.energy < self.energy_costs.mine {
+            self.mining = 0;
+            return None;
+        }
+        self.mining += 1;
+        if self.mining < MINING_TICKS {
+            return None;
+        }
+        self.mining = 0;
+        self.energy -= self.energy_costs.mine;
 
-    pub fn collect_research(&mut self, map: &mut Map) {
-            self.known_map.blueprint[self.x as usize][self.y as usize].resource = Resource::None;
-            map.blueprint[self.x as usize][self.y as usize].resource = Resource::None;
+        let amount = map.extract(x, y, self.cargo_capacity() - self.cargo());
+        match resource {
+            Resource::Iron => self.iron_collected += amount,
+            Resource::Research => self.research_collected += amount,
+            Resource::None => {},
+        }
+        self.known_map.blueprint[x][y] = TileInfo { seen: tick, ..map.blueprint[x][y].clone() };
+        Some((resource, amount))
     }
 
     pub fn moving(&mut self, deplacement: Option<(Vec<(i32, i32)>, u32)>) {
         if let Some((mut path, _)) = deplacement {
             if !path.is_empty() {
-                if self.modules.as_deref() == Some("wheels") && path.len() >= 2 {
-                    let (_bonus_x, _bonus_y) = path.remove(0);
-                    let (next_x, next_y) = path.remove(0);
-                    self.x = next_x;
-                    self.y = next_y;
-                    self.energy -= 1;
-                } else {
-                    let (next_x, next_y) = path.remove(0);
-                    self.x = next_x;
-                    self.y = next_y;
-                    self.energy -= 1;
-                }
+                let here = self.known_map.blueprint[self.x as usize][self.y as usize].biome;
+                let steps = self.module().speed(here).min(path.len());
+                let cells: Vec<(i32, i32)> = path.drain(..steps).collect();
+                self.advance(&cells);
                 self.path = Some(path);
             }
         }
     }
 
-    pub fn update(&mut self, map: &mut Map, base: &mut Base, delta_time: Duration) {
-        self.move_cooldown -= delta_time.as_secs_f32();
+    // Parcourt les cases données, en payant l'entrée sur chacune, alourdie par la cargaison
+    fn advance(&mut self, cells: &[(i32, i32)]) {
+        let mut from = (self.x, self.y);
+        let mut cost = 0;
+        let load = self.load();
+        for &(x, y) in cells {
+            let tile_cost = self.step_cost(&self.known_map.blueprint[x as usize][y as usize]).unwrap_or(1) + load;
+            cost += Topology::step_cost(tile_cost, (x - from.0, y - from.1));
+            from = (x, y);
+        }
+        if let Some(&(x, y)) = cells.last() {
+            self.x = x;
+            self.y = y;
+            self.mining = 0;
+        }
+        self.energy -= cost as i32;
+    }
 
-        if self.move_cooldown > 0.0 {
+    // Suit le chemin jusqu'au prochain point de passage sans croiser les autres robots, en réservant les cases empruntées
+    fn follow_cooperatively(&mut self, path: Vec<(i32, i32)>, base: &mut Base, slot: u64) {
+        if path.is_empty() {
+            self.path = None;
+            self.wait(base, slot);
             return;
         }
+        // Un robot qui rentre sans marge pour un détour planifie en priorité : les autres lui cèdent les créneaux à venir
+        let homebound = path.last().is_some_and(|&cell| base.is_dock(cell));
+        let priority = if homebound && self.return_energy(base).is_some_and(|cost| self.energy < cost + RETURN_MARGIN) {
+            Priority::Homebound
+        } else {
+            Priority::Normal
+        };
+        let waypoint = path.len().min(WINDOW as usize) - 1;
+        let plan = traffic::plan(self, base, path[waypoint], slot, priority);
+
+        // Un plan qui commence par attendre compte comme un déplacement bloqué : le robot attend sur place, sans jamais
+        // traverser un autre robot, et garde les créneaux suivants de son plan. Un robot qui rentre ne s'écarte pas,
+        // pour ne pas gaspiller l'énergie du retour. Les autres s'écartent s'ils gênent un robot qui passe avant eux,
+        // ou s'ils sont bloqués depuis trop longtemps
+        let actions = match plan {
+            Some(actions) if actions.first().is_some_and(|cells| !cells.is_empty()) => actions,
+            plan => {
+                self.blocked += 1;
+                let yields = !homebound
+                    && (self.blocked >= BLOCKED_LIMIT || base.traffic.is_wanted((self.x, self.y), slot, self.id, priority));
+                if yields && self.step_aside(base, slot, path.first().copied()) {
+                    return;
+                }
+                match plan {
+                    Some(actions) => base.traffic.reserve(self.id, (self.x, self.y), &actions, slot, priority),
+                    None => base.traffic.hold(self.id, (self.x, self.y), slot, priority),
+                }
+                if self.blocked >= DEADLOCK_LIMIT {
+                    self.blocked = 0;
+                    self.path = None;
+                } else {
+                    self.path = Some(path);
+                }
+                return;
+            },
+        };
+        self.blocked = 0;
+        base.traffic.reserve(self.id, (self.x, self.y), &actions, slot, priority);
+
+        let mut remaining: Vec<(i32, i32)> = actions.iter().skip(1).flatten().copied().collect();
+        remaining.extend_from_slice(&path[waypoint + 1..]);
+        self.advance(&actions[0]);
+        self.path = Some(remaining);
+    }
+
+    // Reste sur place, sauf à quai sans rien à y faire : la place est libérée pour ceux qui rentrent.
+    // Hors des points d'amarrage, le robot s'écarte aussi du chemin d'un robot qui passe avant lui
+    fn wait(&mut self, base: &mut Base, slot: u64) {
+        let docked = self.is_docked(base);
+        let idle = self.energy >= self.full_charge() && !base.must_wait(self);
+        let wanted = !docked && base.traffic.is_wanted((self.x, self.y), slot, self.id, Priority::Normal);
+        if !((docked && idle || wanted) && self.step_aside(base, slot, None)) {
+            base.traffic.hold(self.id, (self.x, self.y), slot, Priority::Normal);
+        }
+    }
+
+    // Libère le passage en prenant une case voisine libre autre que la prochaine de son chemin, de préférence
+    // une que personne n'a réservée pour la suite. Le chemin sera replanifié depuis celle-ci
+    fn step_aside(&mut self, base: &mut Base, slot: u64, next: Option<(i32, i32)>) -> bool {
+        let free: Vec<(i32, i32)> = self.neighbors((self.x, self.y), &self.known_map, false)
+            .into_iter()
+            .map(|(cell, _)| cell)
+            .filter(|&cell| Some(cell) != next && base.traffic.is_free(base, cell, slot, self.id, slot, Priority::Normal))
+            .collect();
+        let aside = free.iter()
+            .find(|&&cell| !base.traffic.is_reserved(cell, slot, self.id))
+            .or(free.first());
+        let Some(&cell) = aside else {
+            return false;
+        };
+        base.traffic.reserve(self.id, (self.x, self.y), &[vec![cell]], slot, Priority::Normal);
+        self.advance(&[cell]);
+        self.path = None;
+        true
+    }
 
+    // Décompte le temps écoulé : le robot n'agit qu'une fois son délai entre deux déplacements passé
+    pub fn ready(&mut self, delta_time: Duration) -> bool {
+        self.move_cooldown -= delta_time.as_secs_f32();
+        if self.move_cooldown > 0.0 {
+            return false;
+        }
         self.move_cooldown = 1.0;
+        true
+    }
+
+    // À la base ou à un de ses avant-postes
+    pub fn is_docked(&self, base: &Base) -> bool {
+        base.is_dock((self.x, self.y))
+    }
+
+    pub fn full_charge(&self) -> i32 {
+        self.battery * FULL_CHARGE / 100
+    }
+
+    // Un robot à plat ne bouge plus, mais peut encore se recharger s'il a atteint la base
+    pub fn is_stranded(&self, base: &Base) -> bool {
+        self.energy <= 0 && !self.is_docked(base)
+    }
 
-        if self.energy <= 0 {
+    pub fn dock(&mut self, base: &mut Base) {
+        if !self.is_docked(base) {
             return;
         }
+        base.deposit_resources(self);
+        base.share_map(self);
+        self.home_field = None;
+        base.refit(self);
+        base.recharge_robot(self);
 
-        if self.x == base.x && self.y == base.y {
-            base.deposit_resources(self);
-            base.recharge_robot(self);
+        if !self.modified {
+            base.modify_robot_equipment(self);
+        }
 
-            if !self.modified {
-                base.modify_robot_equipment(self);
-            }
+        if self.energy < self.full_charge() {
+            self.modified = false;
+        }
+    }
 
-            if self.energy < 95 {
-                self.modified = false;
-                return;
+    // Ne lit que le robot et la base : les décisions d'un même tick peuvent être prises en parallèle.
+    // Le champ de retour de l'équipement doit avoir été préparé auparavant
+    pub fn decide(&self, base: &Base) -> Option<Action> {
+        if self.is_stranded(base) {
+            return None;
+        }
+        Some(self.strategy.behaviour().decide(&View { robot: self, base }))
+    }
+
+    // Case visée par l'action, si elle porte la ressource ciblée par la classe
+    fn target_of(&self, action: &Action) -> Option<(i32, i32)> {
+        let destination = match action {
+            Action::Follow(path) => path.last().copied().unwrap_or((self.x, self.y)),
+            Action::Stay => (self.x, self.y),
+        };
+        let target = self.class.behaviour().target_resource();
+        let resource = self.known_map.blueprint[destination.0 as usize][destination.1 as usize].resource;
+        Some(destination).filter(|_| target == Some(resource))
+    }
+
+    // Applique la décision sur la base partagée; les robots agissent l'un après l'autre
+    pub fn act(&mut self, decision: Option<Action>, base: &mut Base, tick: u64) {
+        let slot = tick / ACTION_TICKS;
+        let Some(mut action) = decision else {
+            // À plat en chemin, le robot n'atteindra pas sa cible : les autres peuvent la prendre
+            base.claim(self.id, None);
+            if self.avoid_collisions {
+                base.traffic.hold(self.id, (self.x, self.y), slot, Priority::Stranded);
             }
+            return;
+        };
+        // Un robot passé avant lui pendant ce tick a pu réserver le même gisement : la décision est reprise
+        if self.target_of(&action).is_some_and(|target| base.is_claimed(target, self.id)) {
+            action = self.decide(base).unwrap_or(Action::Stay);
         }
+        // La destination est réservée tant qu'elle porte la ressource ciblée, la réservation précédente est abandonnée sinon
+        base.claim(self.id, self.target_of(&action));
+        let previous = (self.x, self.y);
+        let stays = matches!(action, Action::Stay);
 
-        if let Some((path_to_base, base_cost)) = self.path_finding(base.x, base.y, map) {
-            if self.energy < (base_cost as i32 + 5) {
-                if !path_to_base.is_empty() && path_to_base[0] == (self.x, self.y) {
-                    let mut path = path_to_base.clone();
+        match action {
+            Action::Stay => {
+                self.path = None;
+                if self.avoid_collisions {
+                    self.wait(base, slot);
+                }
+            },
+            Action::Follow(mut path) => {
+                if !path.is_empty() && path[0] == (self.x, self.y) {
                     path.remove(0);
-                    self.path = Some(path.clone());
-                    self.moving(Some((path, base_cost)));
+                }
+                if self.avoid_collisions {
+                    self.follow_cooperatively(path, base, slot);
                 } else {
-                    self.path = Some(path_to_base.clone());
-                    self.moving(Some((path_to_base, base_cost)));
+                    self.moving(Some((path, 0)));
                 }
-                return;
-            }
+            },
+        }
+        // Rester sur place loin des points d'amarrage coûte aussi, sauf sur un gisement en cours d'extraction.
+        // Un robot retenu par les autres sur son chemin ne paie pas : la file d'attente d'un quai ne le met pas à plat
+        if stays && (self.x, self.y) == previous && !self.is_docked(base) && !self.on_target() {
+            self.energy = (self.energy - self.energy_costs.idle).max(0);
         }
+    }
 
-        let target_resource = match self.class.as_deref() {
-            Some("scientist") => Resource::Research,
-            _ => Resource::Iron,
+    // Sur un gisement de la ressource ciblée par la classe, avec de la place dans la soute
+    pub fn on_target(&self) -> bool {
+        let resource = self.known_map.blueprint[self.x as usize][self.y as usize].resource;
+        self.class.behaviour().target_resource() == Some(resource) && self.has_room()
+    }
 
-        };
+    pub fn path_to_target(&self, base: &Base) -> Option<(Vec<(i32, i32)>, u32)> {
+        self.class.behaviour().target_resource().and_then(|resource| self.path_to_resource(resource, base))
+    }
 
-        if let Some(mut stored_path) = self.path.take() {
-            if !stored_path.is_empty() {
-                self.moving(Some((stored_path.clone(), 0)));
-                return;
-            }
+    pub fn explore(&self) -> Option<(Vec<(i32, i32)>, u32)> {
+        self.class.behaviour().explore(self)
+    }
+
+    // Une seule propagation depuis le robot, arrêtée au premier gisement libre atteint : c'est le moins coûteux
+    pub fn path_to_resource(&self, resource: Resource, base: &Base) -> Option<(Vec<(i32, i32)>, u32)> {
+        let map = &self.known_map;
+        dijkstra(
+            &(self.x, self.y),
+            |&p| self.neighbors(p, map, true),
+            |&(x, y)| map.blueprint[x as usize][y as usize].resource == resource && !base.is_claimed((x, y), self.id),
+        )
+    }
+
+    // Un relevé ne coûte de l'énergie que s'il révèle des cases que le robot ne connaissait pas,
+    // et le champ de retour propre au robot est alors à refaire
+    pub fn discover_current_location(&mut self, map: &Map, tick: u64) {
+        let (x, y) = (self.x as usize, self.y as usize);
+        if self.known_map.count_unknown(x, y, self.sensor_radius) > 0 {
+            self.energy = (self.energy - self.energy_costs.scan).max(0);
+            self.home_field = None;
+        }
+        self.known_map.discover_area(map, self.x as usize, self.y as usize, self.sensor_radius, tick);
+    }
+
+    pub fn cargo(&self) -> i32 {
+        self.iron_collected + self.research_collected
+    }
+
+    // Les modules agrandissent la soute, mais n'en donnent pas à une classe qui n'en a pas
+    pub fn cargo_capacity(&self) -> i32 {
+        match self.class.behaviour().cargo_capacity() {
+            0 => 0,
+            capacity => capacity + self.module().cargo_bonus(),
+        }
+    }
+
+    pub fn has_room(&self) -> bool {
+        self.cargo() < self.cargo_capacity()
+    }
+
+    // Une classe sans soute n'est jamais pleine : elle n'a pas à rentrer pour décharger
+    pub fn is_full(&self) -> bool {
+        self.cargo_capacity() > 0 && !self.has_room()
+    }
+
+    pub fn module(&self) -> &'static dyn ModuleBehaviour {
+        match self.modules {
+            Some(module) => module.behaviour(),
+            None => &Bare,
         }
+    }
+
+    // Les cases inconnues sont supposées praticables, au coût le plus bas, jusqu'à ce qu'elles soient découvertes
+    pub fn can_cross(&self, tile: &TileInfo) -> bool {
+        self.step_cost(tile).is_some()
+    }
+
+    pub fn step_cost(&self, tile: &TileInfo) -> Option<u32> {
+        self.module().energy_cost(tile.biome)
+    }
+
+    pub fn max_speed(&self) -> usize {
+        let module = self.module();
+        BIOMES.iter().map(|&biome| module.speed(biome)).max().unwrap_or(1)
+    }
+
+    // Énergie maximale qu'un seul déplacement peut coûter avec l'équipement actuel
+    pub fn max_move_cost(&self) -> i32 {
+        let module = self.module();
+        let speed = self.max_speed();
+        let cost = BIOMES.iter().filter_map(|&biome| module.energy_cost(biome)).max().unwrap_or(1) + self.load();
+        let diagonal = self.topology.deltas().iter().map(|&delta| Topology::step_cost(cost, delta)).max().unwrap_or(cost);
+        (speed as u32 * diagonal) as i32
+    }
+
+    pub fn path_is_clear(&self, path: &[(i32, i32)]) -> bool {
+        path.iter().all(|&(x, y)| self.can_cross(&self.known_map.blueprint[x as usize][y as usize]))
+    }
+
+    fn neighbors(&self, p: (i32, i32), map: &Map, allow_unknown: bool) -> Vec<((i32, i32), u32)> {
+        neighbors(self.module(), self.topology, p, map, allow_unknown)
+    }
 
-        let mut closest: Option<(Vec<(i32, i32)>, u32)> = None;
-        for (ix, row) in map.blueprint.iter().enumerate() {
-            for (iy, tile) in row.iter().enumerate() {
-                if tile.resource == target_resource {
-                    if let Some(p) = self.path_finding(ix as i32, iy as i32, map) {
-                        if closest.is_none() || p.1 < closest.as_ref().unwrap().1 {
-                            closest = Some(p);
-                        }
-                    }
+    // Case inconnue la moins coûteuse à atteindre, au coût en énergie du trajet
+    pub fn path_to_unknown(&self) -> Option<(Vec<(i32, i32)>, u32)> {
+        let map = &self.known_map;
+        dijkstra(
+            &(self.x, self.y),
+            |&p| self.neighbors(p, map, true),
+            |&(x, y)| map.blueprint[x as usize][y as usize].biome == Biome::Unknown,
+        )
+    }
+    
+    // Exploration par frontières : case connue bordant l'inconnu qui révèle le plus de cases pour le trajet
+    pub fn path_to_frontier(&self) -> Option<(Vec<(i32, i32)>, u32)> {
+        let map = &self.known_map;
+        let start = (self.x, self.y);
+        let reached = dijkstra_all(&start, |&p| self.neighbors(p, map, false));
+
+        let mut best: Option<((i32, i32), u32, f64)> = None;
+        for (x, column) in map.blueprint.iter().enumerate() {
+            for (y, tile) in column.iter().enumerate() {
+                let cell = (x as i32, y as i32);
+                if tile.biome == Biome::Unknown || cell == start {
+                    continue;
+                }
+                let Some(&(_, cost)) = reached.get(&cell) else {
+                    continue;
+                };
+                let gain = map.count_unknown(x, y, self.sensor_radius);
+                if gain == 0 {
+                    continue;
+                }
+                let score = gain as f64 / (cost as f64 + 1.0);
+                if best.is_none_or(|(_, _, best_score)| score > best_score) {
+                    best = Some((cell, cost, score));
                 }
             }
         }
 
-        if let Some((mut path, cost)) = closest {
-            if !path.is_empty() && path[0] == (self.x, self.y) {
-                path.remove(0);
-            }
-            self.path = Some(path.clone());
-            self.moving(Some((path, cost)));
+        best.map(|(cell, cost, _)| (build_path(&cell, &reached), cost))
+    }
+
+    // Au-delà de ce que la base connaît, le robot lit son retour dans le champ de la base prolongé sur sa carte,
+    // gardé tant que ni sa carte ni celle de la base ne s'agrandissent et que les points d'amarrage restent les mêmes
+    pub fn prepare_home_field(&mut self, base: &Base) {
+        let Some(field) = base.return_field(self.modules, self.topology) else {
+            return;
+        };
+        if field.cost((self.x, self.y)).is_some() {
+            return;
+        }
+        let revision = base.fields_revision();
+        if matches!(self.home_field, Some((computed, _)) if computed == revision) {
+            return;
         }
+        let extended = field.extend(&self.known_map, self.module(), self.topology);
+        self.home_field = Some((revision, extended));
     }
 
-    pub fn discover_current_location(&mut self, biome: Biome, resource: Resource) {
-        self.known_map.discover_area(self.x as usize, self.y as usize, 2, biome, resource);
+    // Champ où lire le retour depuis la position du robot : celui de la base, sinon celui du robot
+    fn home_field<'a>(&'a self, base: &'a Base) -> Option<&'a ReturnField> {
+        let position = (self.x, self.y);
+        base.return_field(self.modules, self.topology)
+            .filter(|field| field.cost(position).is_some())
+            .or_else(|| self.home_field.as_ref().map(|(_, field)| field).filter(|field| field.cost(position).is_some()))
     }
 
-    pub fn move_random(&mut self, map: &Map) {
-        if self.energy <= 0 {
-            return;
+    // Coût du retour au point d'amarrage le plus proche, lu dans le champ de la base ou dans celui du robot,
+    // ou calculé sur la carte du robot si aucun champ n'a été préparé
+    pub fn return_cost(&self, base: &Base) -> Option<u32> {
+        match self.home_field(base) {
+            Some(field) => field.cost((self.x, self.y)),
+            None => self.path_to_nearest_dock(base).map(|(_, cost)| cost),
         }
+    }
 
-        if let Some(mut stored_path) = self.path.take() {
-            if !stored_path.is_empty() {
-                self.moving(Some((stored_path.clone(), 0)));
-                return;
-            }
+    // Énergie du retour avec la cargaison rapportée. Chaque case coûte au moins 1 : le coût du terrain majore
+    // le nombre de cases à parcourir, et donc le surcoût de la cargaison. Les relevés des capteurs ne sont comptés
+    // que hors du champ de la base, quand le chemin passe par des cases qu'elle ne connaît pas
+    pub fn return_energy(&self, base: &Base) -> Option<i32> {
+        let load = self.return_load();
+        let known = base.return_field(self.modules, self.topology).and_then(|field| field.cost((self.x, self.y)));
+        if let Some(cost) = known {
+            return Some((cost * (1 + load)) as i32);
         }
+        let (path, cost) = self.path_home(base)?;
+        let scans = path.iter().skip(1)
+            .filter(|&&(x, y)| self.known_map.count_unknown(x as usize, y as usize, self.sensor_radius) > 0)
+            .count() as i32;
+        Some((cost * (1 + load)) as i32 + scans * self.energy_costs.scan)
+    }
 
-        let mut closest: Option<(Vec<(i32, i32)>, u32)> = None;
-        for (ix, row) in map.blueprint.iter().enumerate() {
-            for (iy, tile) in row.iter().enumerate() {
-                if let Resource::Iron = tile.resource {
-                    if let Some(p) = self.path_finding(ix as i32, iy as i32, map) {
-                        if closest.is_none() || p.1 < closest.as_ref().unwrap().1 {
-                            closest = Some(p);
-                        }
-                    }
-                }
-            }
+    // Surcoût de chaque case parcourue dû à la cargaison
+    pub fn load(&self) -> u32 {
+        self.energy_costs.load(self.cargo())
+    }
+
+    // Surcoût de chaque case parcourue avec la cargaison rapportée : sur un gisement ciblé, la prochaine passe y est déjà comptée
+    pub fn return_load(&self) -> u32 {
+        let mut cargo = self.cargo();
+        if self.on_target() {
+            let richness = self.known_map.blueprint[self.x as usize][self.y as usize].richness;
+            cargo = (cargo + richness).min(self.cargo_capacity());
         }
+        self.energy_costs.load(cargo)
+    }
 
-        if let Some((mut path, cost)) = closest {
-            if !path.is_empty() && path[0] == (self.x, self.y) {
-                path.remove(0);
-            }
-            self.path = Some(path.clone());
-            self.moving(Some((path, cost)));
+    pub fn path_home(&self, base: &Base) -> Option<(Vec<(i32, i32)>, u32)> {
+        match self.home_field(base) {
+            Some(field) => field.path((self.x, self.y)),
+            None => self.path_to_nearest_dock(base),
         }
     }
-    
-    pub fn path_finding(&self, dest_x: i32, dest_y: i32, map: &Map) -> Option<(Vec<(i32, i32)>, u32)> {
+
+    fn path_to_nearest_dock(&self, base: &Base) -> Option<(Vec<(i32, i32)>, u32)> {
+        base.docks()
+            .filter_map(|(x, y)| self.path_through_known(x, y))
+            .min_by_key(|(_, cost)| *cost)
+    }
+
+    // Chemin garanti : uniquement par des cases déjà découvertes, utilisé pour le retour à la base
+    pub fn path_through_known(&self, dest_x: i32, dest_y: i32) -> Option<(Vec<(i32, i32)>, u32)> {
         let start = (self.x, self.y);
         let goal = (dest_x, dest_y);
         astar(
             &start,
-            |&(x, y)| {
-                let deltas = [(1, 0), (-1, 0), (0, 1), (0, -1)];
-                let mut neighbors = Vec::new();
-                for &(dx, dy) in &deltas {
-                    let next = (x + dx, y + dy);
-                    if next.0 < 0 || next.1 < 0 || next.0 >= map.width || next.1 >= map.height {
-                        continue;
-                    }
-                    let tile = &map.blueprint[next.0 as usize][next.1 as usize];
-                    if let Biome::Mountain = tile.biome {
-                        if self.modules.as_deref() != Some("tracks") {
-                            continue;
-                        }
-                    }
-                    if let Biome::Water = tile.biome {
-                        if self.modules.as_deref() != Some("buoy") {
-                            continue;
-                        }
-                    }
-                    neighbors.push((next, 1));
-                }
-                neighbors
-            },
-            |&(x, y)| goal.0.abs_diff(x) + goal.1.abs_diff(y),
+            |&p| self.neighbors(p, &self.known_map, false),
+            |&p| self.topology.distance(p, goal),
             |&p| p == goal,
         )
     }
+}
+
+// Coût pour entrer sur la case, None si elle est hors carte, infranchissable ou inconnue sans `allow_unknown`
+pub fn enter_cost(module: &dyn ModuleBehaviour, (x, y): (i32, i32), map: &Map, allow_unknown: bool) -> Option<u32> {
+    if x < 0 || y < 0 || x >= map.width || y >= map.height {
+        return None;
+    }
+    let tile = &map.blueprint[x as usize][y as usize];
+    if !allow_unknown && tile.biome == Biome::Unknown {
+        return None;
+    }
+    module.energy_cost(tile.biome)
+}
+
+// Une diagonale n'est permise que si les deux cases qu'elle longe sont praticables, pour ne pas couper d'angle
+pub fn neighbors(module: &dyn ModuleBehaviour, topology: Topology, (x, y): (i32, i32), map: &Map, allow_unknown: bool) -> Vec<((i32, i32), u32)> {
+    let mut neighbors = Vec::new();
+    for &(dx, dy) in topology.deltas() {
+        let next = (x + dx, y + dy);
+        let Some(cost) = enter_cost(module, next, map, allow_unknown) else {
+            continue;
+        };
+        if dx != 0 && dy != 0
+            && (enter_cost(module, (x + dx, y), map, allow_unknown).is_none()
+                || enter_cost(module, (x, y + dy), map, allow_unknown).is_none()) {
+            continue;
+        }
+        neighbors.push((next, Topology::step_cost(cost, (dx, dy))));
+    }
+    neighbors
+}
+
+pub fn render_at(grid: &mut [Vec<Span>], x: i32, y: i32) {
+    grid[y as usize][x as usize] = Span::styled(
+        "R",
+        Style::default()
+            .fg(Color::Red)
+            .add_modifier(Modifier::BOLD)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::robots::module::Bare;
 
-    pub fn render(&self, grid: &mut Vec<Vec<Span>>) {
-        let x = self.x as usize;
-        let y = self.y as usize;
-        grid[y][x] = Span::styled(
-            "R",
-            Style::default()
-                .fg(Color::Red)
-                .add_modifier(Modifier::BOLD)
-        );
+    fn plain(width: i32, height: i32) -> Map {
+        let mut map = Map::unknown(0, width, height);
+        for column in map.blueprint.iter_mut() {
+            for tile in column.iter_mut() {
+                tile.biome = Biome::Plain;
+            }
+        }
+        map
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn diagonals_only_in_eight_directions_and_at_a_higher_cost() {
+        let map = plain(3, 3);
+        assert_eq!(neighbors(&Bare, Topology::Four, (1, 1), &map, false).len(), 4);
+        let around = neighbors(&Bare, Topology::Eight, (1, 1), &map, false);
+        assert_eq!(around.len(), 8);
+        assert!(around.contains(&((2, 2), 3)));
+        assert!(around.contains(&((2, 1), 2)));
+    }
+
+    #[test]
+    fn diagonals_do_not_cut_corners() {
+        let mut map = plain(3, 3);
+        map.blueprint[2][1].biome = Biome::Mountain;
+        let around: Vec<(i32, i32)> = neighbors(&Bare, Topology::Eight, (1, 1), &map, false).into_iter().map(|(cell, _)| cell).collect();
+        assert!(!around.contains(&(2, 1)));
+        assert!(!around.contains(&(2, 0)));
+        assert!(!around.contains(&(2, 2)));
+        assert!(around.contains(&(0, 0)));
+    }
+
+    #[test]
+    fn unknown_tiles_are_priced_in_energy() {
+        let (map, _) = crate::maps::map::generate_map(1, 9, 9);
+        let mut robot = Robot::new(map.width, map.height, &map, RobotClass::Miner);
+        robot.known_map = plain(9, 9);
+        robot.known_map.blueprint[6][4].biome = Biome::Unknown;
+        let (path, cost) = robot.path_to_unknown().expect("an unknown tile is reachable");
+        assert_eq!(path.last(), Some(&(6, 4)));
+        assert_eq!(cost, 2 * (path.len() as u32 - 1));
+    }
+
+    #[test]
+    fn stranded_robots_give_up_their_claim() {
+        let (map, _) = crate::maps::map::generate_map(1, 20, 20);
+        let mut base = Base::new(&map, &crate::simulation::config::BaseConfig::default());
+        let mut robot = Robot::new(map.width, map.height, &map, RobotClass::Miner);
+        robot.x += 3;
+        robot.energy = 0;
+        base.claim(robot.id, Some((robot.x + 1, robot.y)));
+
+        robot.act(None, &mut base, 0);
+        assert!(base.claims.is_empty());
+    }
+}
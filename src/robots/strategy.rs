@@ -0,0 +1,194 @@
+use std::fmt;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::maps::map::{Biome, Resource};
+use crate::base::base::Base;
+use super::robot::Robot;
+
+// Part de la batterie (en %) à atteindre avant de quitter la base, et marge gardée pour le retour
+pub const FULL_CHARGE: i32 = 95;
+pub const RETURN_MARGIN: i32 = 5;
+// Coût en énergie en dessous duquel la stratégie exploratoire préfère collecter une ressource connue
+const EXPLOIT_RANGE: u32 = 6;
+
+// Ce que le robot sait au moment de décider
+pub struct View<'a> {
+    pub robot: &'a Robot,
+    pub base: &'a Base,
+}
+
+pub enum Action {
+    Stay,
+    Follow(Vec<(i32, i32)>),
+}
+
+pub trait Strategy {
+    fn name(&self) -> &'static str;
+    fn decide(&self, view: &View) -> Action;
+}
+
+pub struct Greedy;
+pub struct Exploratory;
+pub struct Cooperative;
+
+impl Strategy for Greedy {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn decide(&self, view: &View) -> Action {
+        if let Some(action) = return_to_base(view)
+            .or_else(|| keep_mining(view))
+            .or_else(|| report(view))
+            .or_else(|| stored_path(view)) {
+            return action;
+        }
+        let robot = view.robot;
+        robot.path_to_target(view.base)
+            .or_else(|| robot.explore())
+            .map_or(Action::Stay, |trip| go_if_affordable(view, trip))
+    }
+}
+
+impl Strategy for Exploratory {
+    fn name(&self) -> &'static str {
+        "exploratory"
+    }
+
+    fn decide(&self, view: &View) -> Action {
+        if let Some(action) = return_to_base(view)
+            .or_else(|| keep_mining(view))
+            .or_else(|| report(view))
+            .or_else(|| stored_path(view)) {
+            return action;
+        }
+        let robot = view.robot;
+        match robot.path_to_target(view.base) {
+            Some(trip) if trip.1 <= EXPLOIT_RANGE => go_if_affordable(view, trip),
+            target => robot.explore()
+                .or(target)
+                .map_or(Action::Stay, |trip| go_if_affordable(view, trip)),
+        }
+    }
+}
+
+impl Strategy for Cooperative {
+    fn name(&self) -> &'static str {
+        "cooperative"
+    }
+
+    // Rentre signaler les ressources utiles aux autres classes dès qu'il en découvre
+    fn decide(&self, view: &View) -> Action {
+        if let Some(action) = return_to_base(view) {
+            return action;
+        }
+        if has_news_for_others(view) {
+            if let Some((path, _)) = view.robot.path_home(view.base) {
+                return Action::Follow(path);
+            }
+        }
+        Greedy.decide(view)
+    }
+}
+
+// Rester à quai jusqu'à la recharge complète, ou rentrer si l'énergie ne suffit plus pour le retour
+// ou si la soute est pleine et que la base peut la décharger. Un déplacement de plus peut à la fois coûter de l'énergie et allonger le retour,
+// d'où la réserve de deux déplacements en plus de la marge
+fn return_to_base(view: &View) -> Option<Action> {
+    let (robot, base) = (view.robot, view.base);
+    if robot.is_docked(base) && (robot.energy < robot.full_charge() || base.must_wait(robot)) {
+        return Some(Action::Stay);
+    }
+    let reserve = RETURN_MARGIN + 2 * robot.max_move_cost();
+    let cost = robot.return_energy(base)?;
+    if robot.energy < cost + reserve || (robot.is_full() && !base.holds_out(robot)) {
+        robot.path_home(base).map(|(path, _)| Action::Follow(path))
+    } else {
+        None
+    }
+}
+
+// Rester sur un gisement de la ressource ciblée tant qu'il n'est pas vide et que la soute a de la place
+fn keep_mining(view: &View) -> Option<Action> {
+    let robot = view.robot;
+    let tile = &robot.known_map.blueprint[robot.x as usize][robot.y as usize];
+    let target = robot.class.behaviour().target_resource()?;
+    (tile.resource == target && !robot.is_full()).then_some(Action::Stay)
+}
+
+// Les classes qui cartographient rentrent partager leurs découvertes une fois assez de cases inconnues de la base révélées
+fn report(view: &View) -> Option<Action> {
+    let threshold = view.robot.class.behaviour().report_threshold()?;
+    let discovered = view.robot.known_map.blueprint.iter().zip(&view.base.known_map.blueprint)
+        .map(|(column, base_column)| {
+            column.iter().zip(base_column)
+                .filter(|(tile, base_tile)| tile.biome != Biome::Unknown && base_tile.biome == Biome::Unknown)
+                .count()
+        })
+        .sum::<usize>();
+    if discovered < threshold {
+        return None;
+    }
+    view.robot.path_home(view.base).map(|(path, _)| Action::Follow(path))
+}
+
+// Loin des points d'amarrage, une nouvelle destination n'est prise que si l'énergie suffit pour l'atteindre,
+// y faire une passe d'extraction et rentrer ensuite (au coût de l'aller quand la base ne connaît pas le retour).
+// Sinon le robot rentre d'abord se recharger plutôt que de tomber en panne en chemin, ou attend s'il ne connaît pas le retour
+fn go_if_affordable(view: &View, (path, cost): (Vec<(i32, i32)>, u32)) -> Action {
+    let (robot, base) = (view.robot, view.base);
+    if robot.is_docked(base) {
+        return Action::Follow(path);
+    }
+    let destination = path.last().copied().unwrap_or((robot.x, robot.y));
+    let back = base.return_field(robot.modules, robot.topology)
+        .and_then(|field| field.cost(destination))
+        .unwrap_or(cost);
+    let needed = (cost + back) as i32 * (1 + robot.return_load() as i32) + robot.energy_costs.mine + RETURN_MARGIN;
+    if robot.energy >= needed {
+        return Action::Follow(path);
+    }
+    robot.path_home(base).map_or(Action::Stay, |(home, _)| Action::Follow(home))
+}
+
+fn stored_path(view: &View) -> Option<Action> {
+    match &view.robot.path {
+        Some(path) if !path.is_empty() && view.robot.path_is_clear(path) => Some(Action::Follow(path.clone())),
+        _ => None,
+    }
+}
+
+fn has_news_for_others(view: &View) -> bool {
+    let own_target = view.robot.class.behaviour().target_resource();
+    view.robot.known_map.blueprint.iter().zip(&view.base.known_map.blueprint).any(|(column, base_column)| {
+        column.iter().zip(base_column).any(|(tile, base_tile)| {
+            tile.resource != Resource::None
+                && Some(tile.resource) != own_target
+                && (base_tile.biome == Biome::Unknown || base_tile.resource != tile.resource)
+        })
+    })
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StrategyKind {
+    Greedy,
+    Exploratory,
+    Cooperative,
+}
+
+impl StrategyKind {
+    pub fn behaviour(self) -> &'static dyn Strategy {
+        match self {
+            StrategyKind::Greedy => &Greedy,
+            StrategyKind::Exploratory => &Exploratory,
+            StrategyKind::Cooperative => &Cooperative,
+        }
+    }
+}
+
+impl fmt::Display for StrategyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.behaviour().name())
+    }
+}
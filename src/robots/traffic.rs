@@ -0,0 +1,270 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use pathfinding::prelude::astar;
+use serde::{Deserialize, Serialize};
+use crate::base::base::Base;
+use super::robot::{self, Robot};
+
+// Nombre de déplacements à venir réservés par chaque robot, et planifiés en tenant compte des autres
+pub const WINDOW: u64 = 6;
+// Au-delà, le point de passage est jugé inaccessible pour l'instant
+const MAX_ACTIONS: u32 = WINDOW as u32 + WINDOW as u32 / 2;
+
+// Rang d'une réservation : un robot qui rentre sans marge pour un détour passe avant les autres,
+// et personne ne passe avant un robot à plat, qui ne peut plus s'écarter
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    Normal,
+    Homebound,
+    Stranded,
+}
+
+// Ordre de passage : le rang d'abord, puis à rang égal le plus petit identifiant. Pour les créneaux à venir,
+// un robot passe outre les réservations de ceux qu'il précède, qui s'écartent ou replanifient
+fn precedes((priority, robot): (Priority, i32), (rank, other): (Priority, i32)) -> bool {
+    (priority, Reverse(robot)) > (rank, Reverse(other))
+}
+
+// Case occupée par un robot pendant un créneau, c'est-à-dire un de ses déplacements
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Reservation {
+    robot: i32,
+    x: i32,
+    y: i32,
+    slot: u64,
+    #[serde(default)]
+    priority: Priority,
+}
+
+// Robot, créneau et rang d'une réservation sur une case
+type Entry = (i32, u64, Priority);
+
+// Table des réservations espace-temps, partagée par tous les robots à travers la base.
+// Indexée par case pour les recherches de l'A*, sauvegardée comme une simple liste
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<Reservation>", into = "Vec<Reservation>")]
+pub struct Traffic {
+    cells: HashMap<(i32, i32), Vec<Entry>>,
+}
+
+impl From<Vec<Reservation>> for Traffic {
+    fn from(reservations: Vec<Reservation>) -> Self {
+        let mut traffic = Traffic::default();
+        for Reservation { robot, x, y, slot, priority } in reservations {
+            traffic.cells.entry((x, y)).or_default().push((robot, slot, priority));
+        }
+        traffic
+    }
+}
+
+impl From<Traffic> for Vec<Reservation> {
+    fn from(traffic: Traffic) -> Self {
+        let mut reservations: Vec<Reservation> = traffic.cells.into_iter()
+            .flat_map(|((x, y), entries)| {
+                entries.into_iter().map(move |(robot, slot, priority)| Reservation { robot, x, y, slot, priority })
+            })
+            .collect();
+        reservations.sort_by_key(|r| (r.slot, r.robot, r.x, r.y));
+        reservations
+    }
+}
+
+impl Traffic {
+    // Nombre de robots admis sur la case : plusieurs à quai (à la base comme aux avant-postes), un seul ailleurs
+    fn capacity(base: &Base, cell: (i32, i32)) -> usize {
+        if base.is_dock(cell) { base.docking_slots.max(1) as usize } else { 1 }
+    }
+
+    // Pour le créneau qui commence, un robot qui n'a pas encore bougé occupe encore sa case du créneau précédent.
+    // Ce créneau-là est toujours respecté : la priorité ne vaut que pour les suivants, sans jamais traverser un robot
+    pub fn is_free(&self, base: &Base, cell: (i32, i32), slot: u64, robot: i32, now: u64, priority: Priority) -> bool {
+        let Some(entries) = self.cells.get(&cell) else {
+            return true;
+        };
+        let mut taken: Vec<i32> = entries.iter()
+            .filter(|&&(other, reserved, rank)| {
+                other != robot
+                    && (reserved == slot || (slot == now && reserved + 1 == slot))
+                    && (slot == now || !precedes((priority, robot), (rank, other)))
+            })
+            .map(|&(other, _, _)| other)
+            .collect();
+        taken.sort_unstable();
+        taken.dedup();
+        taken.len() < Self::capacity(base, cell)
+    }
+
+    // Remplace les réservations du robot : une liste de cases par déplacement à partir du créneau donné,
+    // un déplacement vide signifiant une attente, puis la case d'arrivée jusqu'à la fin de la fenêtre
+    pub fn reserve(&mut self, robot: i32, start: (i32, i32), actions: &[Vec<(i32, i32)>], slot: u64, priority: Priority) {
+        self.cells.retain(|_, entries| {
+            entries.retain(|&(other, reserved, _)| other != robot && reserved + 1 >= slot);
+            !entries.is_empty()
+        });
+        let mut position = start;
+        for offset in 0..WINDOW.max(actions.len() as u64) {
+            match actions.get(offset as usize) {
+                Some(cells) if !cells.is_empty() => {
+                    for &cell in cells {
+                        self.cells.entry(cell).or_default().push((robot, slot + offset, priority));
+                    }
+                    position = *cells.last().unwrap();
+                },
+                _ => self.cells.entry(position).or_default().push((robot, slot + offset, priority)),
+            }
+        }
+    }
+
+    pub fn hold(&mut self, robot: i32, cell: (i32, i32), slot: u64, priority: Priority) {
+        self.reserve(robot, cell, &[], slot, priority);
+    }
+
+    // Un autre robot a prévu d'occuper la case dans les prochains créneaux
+    pub fn is_reserved(&self, cell: (i32, i32), slot: u64, robot: i32) -> bool {
+        self.cells.get(&cell).is_some_and(|entries| entries.iter().any(|&(other, reserved, _)| other != robot && reserved > slot))
+    }
+
+    // Un robot qui passe avant celui-ci a prévu d'occuper la case dans les prochains créneaux
+    pub fn is_wanted(&self, cell: (i32, i32), slot: u64, robot: i32, priority: Priority) -> bool {
+        self.cells.get(&cell).is_some_and(|entries| {
+            entries.iter().any(|&(other, reserved, rank)| reserved > slot && precedes((rank, other), (priority, robot)))
+        })
+    }
+}
+
+// État de l'A* espace-temps : case atteinte après un nombre de déplacements. Les cases traversées
+// pendant le dernier déplacement sont gardées pour reconstruire le trajet, sans distinguer deux états
+#[derive(Clone)]
+struct Step {
+    cell: (i32, i32),
+    actions: u32,
+    cells: Vec<(i32, i32)>,
+}
+
+impl PartialEq for Step {
+    fn eq(&self, other: &Self) -> bool {
+        (self.cell, self.actions) == (other.cell, other.actions)
+    }
+}
+
+impl Eq for Step {}
+
+impl Hash for Step {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.cell, self.actions).hash(state);
+    }
+}
+
+// A* espace-temps vers `goal` : chaque déplacement parcourt jusqu'à `speed` cases libres pendant son créneau,
+// ou attend sur place. Un créneau coûte 1, plus l'énergie dépensée, pour préférer attendre à un détour
+// aussi long. Renvoie les cases traversées à chaque déplacement
+pub fn plan(robot: &Robot, base: &Base, goal: (i32, i32), slot: u64, priority: Priority) -> Option<Vec<Vec<(i32, i32)>>> {
+    let start = (robot.x, robot.y);
+    let module = robot.module();
+    let map = &robot.known_map;
+    let max_speed = robot.max_speed() as u32;
+
+    let (states, _) = astar(
+        &Step { cell: start, actions: 0, cells: Vec::new() },
+        |&Step { cell, actions, .. }| {
+            let mut successors = Vec::new();
+            if actions >= MAX_ACTIONS {
+                return successors;
+            }
+            let when = slot + actions as u64;
+            let next = actions + 1;
+            if cell == start || base.traffic.is_free(base, cell, when, robot.id, slot, priority) {
+                successors.push((Step { cell, actions: next, cells: Vec::new() }, 1));
+            }
+
+            let speed = module.speed(map.blueprint[cell.0 as usize][cell.1 as usize].biome);
+            let mut frontier = vec![(vec![cell], 0)];
+            for _ in 0..speed {
+                let mut longer = Vec::new();
+                for (route, energy) in frontier {
+                    let last = *route.last().unwrap();
+                    for (step, cost) in robot::neighbors(module, robot.topology, last, map, true) {
+                        if route.contains(&step) || !base.traffic.is_free(base, step, when, robot.id, slot, priority) {
+                            continue;
+                        }
+                        let mut extended = route.clone();
+                        extended.push(step);
+                        successors.push((Step { cell: step, actions: next, cells: extended[1..].to_vec() }, 1 + energy + cost));
+                        longer.push((extended, energy + cost));
+                    }
+                }
+                frontier = longer;
+            }
+            successors
+        },
+        |step| robot.topology.steps(step.cell, goal).div_ceil(max_speed),
+        |step| step.cell == goal,
+    )?;
+
+    Some(states.into_iter().skip(1).map(|step| step.cells).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::map::{generate_map, Biome};
+    use crate::robots::class::RobotClass;
+    use crate::simulation::config::BaseConfig;
+
+    // Robot sur une carte connue entièrement praticable, loin de la base
+    fn setup() -> (Base, Robot) {
+        let (map, _) = generate_map(1, 20, 20);
+        let base = Base::new(&map, &BaseConfig::default());
+        let mut robot = Robot::new(map.width, map.height, &map, RobotClass::Miner);
+        for column in robot.known_map.blueprint.iter_mut() {
+            for tile in column.iter_mut() {
+                tile.biome = Biome::Plain;
+            }
+        }
+        (robot.id, robot.x, robot.y) = (5, 2, 2);
+        (base, robot)
+    }
+
+    fn visits(plan: &[Vec<(i32, i32)>], cell: (i32, i32), until: usize) -> bool {
+        plan.iter().take(until).any(|cells| cells.contains(&cell))
+    }
+
+    #[test]
+    fn free_road_is_taken_straight() {
+        let (base, robot) = setup();
+        let route = plan(&robot, &base, (5, 2), 10, Priority::Normal).expect("goal is reachable");
+        assert_eq!(route, vec![vec![(3, 2)], vec![(4, 2)], vec![(5, 2)]]);
+    }
+
+    #[test]
+    fn reservations_of_robots_that_go_first_are_avoided() {
+        let (mut base, robot) = setup();
+        base.traffic.hold(0, (4, 2), 10, Priority::Normal);
+        let route = plan(&robot, &base, (5, 2), 10, Priority::Normal).expect("goal is reachable");
+        assert!(!visits(&route, (4, 2), WINDOW as usize));
+        assert_eq!(route.iter().rev().find(|cells| !cells.is_empty()).and_then(|cells| cells.last()), Some(&(5, 2)));
+    }
+
+    #[test]
+    fn homebound_robots_go_first_but_never_through_the_current_slot() {
+        let (mut base, robot) = setup();
+        base.traffic.hold(0, (4, 2), 10, Priority::Normal);
+        let route = plan(&robot, &base, (5, 2), 10, Priority::Homebound).expect("goal is reachable");
+        assert_eq!(route, vec![vec![(3, 2)], vec![(4, 2)], vec![(5, 2)]]);
+
+        let (mut base, robot) = setup();
+        base.traffic.hold(0, (3, 2), 10, Priority::Normal);
+        let route = plan(&robot, &base, (5, 2), 10, Priority::Stranded).expect("goal is reachable");
+        assert!(!visits(&route, (3, 2), 1));
+    }
+
+    #[test]
+    fn ties_go_to_the_smaller_id() {
+        assert!(precedes((Priority::Normal, 1), (Priority::Normal, 2)));
+        assert!(precedes((Priority::Homebound, 7), (Priority::Normal, 2)));
+        assert!(!precedes((Priority::Homebound, 7), (Priority::Stranded, 9)));
+    }
+}
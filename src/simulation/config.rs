@@ -0,0 +1,411 @@
+use std::{error::Error, fmt, fs, io, path::{Path, PathBuf}};
+use clap::Parser;
+use serde::Deserialize;
+use crate::base::base::{OverflowPolicy, OUTPOST_ENERGY_CAPACITY};
+use crate::base::research::TechTree;
+use crate::base::upgrade::Tier;
+use crate::maps::map::Topology;
+use crate::robots::class::RobotClass;
+use crate::robots::energy::EnergyCosts;
+use crate::robots::strategy::StrategyKind;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+#[derive(Parser)]
+#[command(about = "Simulation de robots collecteurs de ressources")]
+pub struct Cli {
+    /// Fichier de configuration TOML (par défaut: config.toml s'il existe)
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// Simulation sans terminal, par ticks fixes, puis affichage des totaux de la base
+    #[arg(long)]
+    pub headless: bool,
+    /// Nombre de ticks simulés en mode headless
+    #[arg(long)]
+    pub ticks: Option<u64>,
+    /// Reprend la simulation depuis une sauvegarde
+    #[arg(long, value_name = "FILE")]
+    pub load: Option<PathBuf>,
+    /// Sauvegarde l'état final du mode headless
+    #[arg(long, value_name = "FILE")]
+    pub save: Option<PathBuf>,
+    /// Enregistre chaque tick dans un journal d'événements
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+    /// Rejoue un journal enregistré avec --record
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["headless", "load", "record"])]
+    pub replay: Option<PathBuf>,
+    /// Arbre de recherche TOML (par défaut: l'arbre intégré, voir techs.toml)
+    #[arg(long, value_name = "FILE")]
+    pub tech_tree: Option<PathBuf>,
+    /// Fichier utilisé par les touches 's' (sauvegarder) et 'l' (charger)
+    #[arg(long, value_name = "FILE")]
+    pub snapshot_file: Option<PathBuf>,
+    #[arg(long)]
+    pub seed: Option<u32>,
+    #[arg(long)]
+    pub width: Option<i32>,
+    #[arg(long)]
+    pub height: Option<i32>,
+    #[arg(long)]
+    pub fps: Option<u64>,
+    #[arg(long)]
+    pub robots: Option<usize>,
+    /// Classes attribuées à tour de rôle aux robots, ex: scientist,miner
+    #[arg(long, value_delimiter = ',')]
+    pub classes: Option<Vec<RobotClass>>,
+    /// Stratégies attribuées à tour de rôle aux robots, ex: greedy,exploratory
+    #[arg(long, value_delimiter = ',')]
+    pub strategies: Option<Vec<StrategyKind>>,
+    /// Déplacements en 4 directions (four) ou avec les diagonales (eight)
+    #[arg(long)]
+    pub topology: Option<Topology>,
+    /// Un seul robot par case, les robots se contournent au lieu de se traverser
+    #[arg(long)]
+    pub collisions: bool,
+    /// Threads utilisés pour les phases parallèles de chaque tick (0: un par cœur)
+    #[arg(long)]
+    pub threads: Option<usize>,
+    #[arg(long)]
+    pub base_energy: Option<i32>,
+    #[arg(long)]
+    pub base_energy_capacity: Option<i32>,
+    #[arg(long)]
+    pub base_iron_storage: Option<i32>,
+    #[arg(long)]
+    pub base_research_storage: Option<i32>,
+    /// Robots admis en même temps à quai quand les collisions sont actives
+    #[arg(long)]
+    pub base_docking_slots: Option<i32>,
+    /// Énergie transférée à un robot à quai à chacune de ses actions (tous les 10 ticks), avant les améliorations de la base
+    #[arg(long)]
+    pub base_charge_rate: Option<i32>,
+    /// Nombre maximal d'avant-postes construits par les robots (0 pour aucun)
+    #[arg(long)]
+    pub base_max_outposts: Option<usize>,
+    /// Fer et énergie dépensés par la base pour construire un robot
+    #[arg(long)]
+    pub factory_iron: Option<i32>,
+    #[arg(long)]
+    pub factory_energy: Option<i32>,
+    /// Durée de construction d'un robot, en ticks
+    #[arg(long)]
+    pub factory_build_ticks: Option<u32>,
+    /// Énergie dépensée par passe d'extraction
+    #[arg(long)]
+    pub energy_mine: Option<i32>,
+    /// Énergie dépensée par relevé des capteurs révélant des cases inconnues
+    #[arg(long)]
+    pub energy_scan: Option<i32>,
+    /// Énergie dépensée par action d'attente loin de la base
+    #[arg(long)]
+    pub energy_idle: Option<i32>,
+    /// Unités de cargaison ajoutant 1 au coût de chaque case parcourue (0 : sans effet)
+    #[arg(long)]
+    pub energy_cargo_weight: Option<i32>,
+    /// Cargaison qui ne tient pas dans les réserves : keep, wait ou discard
+    #[arg(long)]
+    pub base_overflow: Option<OverflowPolicy>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub seed: u32,
+    pub width: i32,
+    pub height: i32,
+    pub fps: u64,
+    pub ticks: u64,
+    pub num_robots: usize,
+    pub classes: Vec<RobotClass>,
+    pub strategies: Vec<StrategyKind>,
+    pub topology: Topology,
+    pub collisions: bool,
+    pub threads: usize,
+    pub snapshot_file: PathBuf,
+    pub tech_tree: Option<PathBuf>,
+    // Projets lus depuis `tech_tree` au chargement de la configuration
+    #[serde(skip)]
+    pub techs: TechTree,
+    pub base: BaseConfig,
+    pub factory: FactoryConfig,
+    pub energy: EnergyCosts,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct BaseConfig {
+    pub energy_capacity: i32,
+    pub energy: i32,
+    pub iron_storage: i32,
+    pub iron: i32,
+    pub research_storage: i32,
+    pub research: i32,
+    pub overflow: OverflowPolicy,
+    pub docking_slots: i32,
+    pub charge_rate: i32,
+    pub max_outposts: usize,
+    pub outpost_iron: i32,
+    pub outpost_energy: i32,
+    pub tiers: Vec<Tier>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct FactoryConfig {
+    pub iron: i32,
+    pub energy: i32,
+    pub build_ticks: u32,
+    pub queue: usize,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "invalid config file {}: {}", path.display(), e),
+            ConfigError::Invalid(msg) => write!(f, "invalid configuration: {}", msg),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            seed: 155,
+            width: 150,
+            height: 40,
+            fps: 30,
+            ticks: 1000,
+            num_robots: 3,
+            classes: vec![RobotClass::Scientist, RobotClass::Miner],
+            strategies: vec![StrategyKind::Greedy],
+            topology: Topology::Four,
+            collisions: false,
+            threads: 0,
+            snapshot_file: PathBuf::from("snapshot.json"),
+            tech_tree: None,
+            techs: TechTree::default(),
+            base: BaseConfig::default(),
+            factory: FactoryConfig::default(),
+            energy: EnergyCosts::default(),
+        }
+    }
+}
+
+impl Default for BaseConfig {
+    fn default() -> Self {
+        Self {
+            energy_capacity: 500,
+            energy: 250,
+            iron_storage: 100,
+            iron: 0,
+            research_storage: 20,
+            research: 0,
+            overflow: OverflowPolicy::Keep,
+            docking_slots: 2,
+            charge_rate: 5,
+            max_outposts: 2,
+            outpost_iron: 30,
+            outpost_energy: 100,
+            tiers: Tier::defaults(),
+        }
+    }
+}
+
+impl Default for FactoryConfig {
+    fn default() -> Self {
+        Self {
+            iron: 20,
+            energy: 100,
+            build_ticks: 300,
+            queue: 5,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(cli: &Cli) -> Result<Self, ConfigError> {
+        let mut config = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Self::default(),
+        };
+        config.apply_cli(cli);
+        if let Some(path) = &config.tech_tree {
+            let content = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+            config.techs = toml::from_str(&content).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        toml::from_str(&content).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    fn apply_cli(&mut self, cli: &Cli) {
+        if let Some(seed) = cli.seed { self.seed = seed; }
+        if let Some(width) = cli.width { self.width = width; }
+        if let Some(height) = cli.height { self.height = height; }
+        if let Some(fps) = cli.fps { self.fps = fps; }
+        if let Some(ticks) = cli.ticks { self.ticks = ticks; }
+        if let Some(robots) = cli.robots { self.num_robots = robots; }
+        if let Some(classes) = &cli.classes { self.classes = classes.clone(); }
+        if let Some(strategies) = &cli.strategies { self.strategies = strategies.clone(); }
+        if let Some(topology) = cli.topology { self.topology = topology; }
+        if cli.collisions { self.collisions = true; }
+        if let Some(threads) = cli.threads { self.threads = threads; }
+        if let Some(file) = &cli.snapshot_file { self.snapshot_file = file.clone(); }
+        if let Some(file) = &cli.tech_tree { self.tech_tree = Some(file.clone()); }
+        if let Some(energy) = cli.base_energy { self.base.energy = energy; }
+        if let Some(capacity) = cli.base_energy_capacity { self.base.energy_capacity = capacity; }
+        if let Some(storage) = cli.base_iron_storage { self.base.iron_storage = storage; }
+        if let Some(storage) = cli.base_research_storage { self.base.research_storage = storage; }
+        if let Some(overflow) = cli.base_overflow { self.base.overflow = overflow; }
+        if let Some(slots) = cli.base_docking_slots { self.base.docking_slots = slots; }
+        if let Some(outposts) = cli.base_max_outposts { self.base.max_outposts = outposts; }
+        if let Some(iron) = cli.factory_iron { self.factory.iron = iron; }
+        if let Some(energy) = cli.factory_energy { self.factory.energy = energy; }
+        if let Some(ticks) = cli.factory_build_ticks { self.factory.build_ticks = ticks; }
+        if let Some(rate) = cli.base_charge_rate { self.base.charge_rate = rate; }
+        if let Some(cost) = cli.energy_mine { self.energy.mine = cost; }
+        if let Some(cost) = cli.energy_scan { self.energy.scan = cost; }
+        if let Some(cost) = cli.energy_idle { self.energy.idle = cost; }
+        if let Some(weight) = cli.energy_cargo_weight { self.energy.cargo_weight = weight; }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: String| Err(ConfigError::Invalid(msg));
+
+        if !(3..=1000).contains(&self.width) {
+            return invalid(format!("width must be between 3 and 1000, got {}", self.width));
+        }
+        if !(3..=1000).contains(&self.height) {
+            return invalid(format!("height must be between 3 and 1000, got {}", self.height));
+        }
+        if !(1..=1000).contains(&self.fps) {
+            return invalid(format!("fps must be between 1 and 1000, got {}", self.fps));
+        }
+        if self.ticks == 0 {
+            return invalid("ticks must be positive".to_string());
+        }
+        if self.classes.is_empty() {
+            return invalid("classes must contain at least one robot class".to_string());
+        }
+        if self.strategies.is_empty() {
+            return invalid("strategies must contain at least one strategy".to_string());
+        }
+
+        let base = &self.base;
+        if base.energy_capacity <= 0 {
+            return invalid(format!("base.energy_capacity must be positive, got {}", base.energy_capacity));
+        }
+        if !(0..=base.energy_capacity).contains(&base.energy) {
+            return invalid(format!("base.energy must be between 0 and base.energy_capacity ({}), got {}", base.energy_capacity, base.energy));
+        }
+        if base.iron_storage < 0 || base.research_storage < 0 {
+            return invalid("base storages cannot be negative".to_string());
+        }
+        if base.iron < 0 || base.research < 0 {
+            return invalid("base starting iron and research cannot be negative".to_string());
+        }
+        if base.iron > base.iron_storage || base.research > base.research_storage {
+            return invalid("base starting iron and research cannot exceed their storage".to_string());
+        }
+        if base.docking_slots < 1 {
+            return invalid(format!("base.docking_slots must be at least 1, got {}", base.docking_slots));
+        }
+        if base.charge_rate < 1 {
+            return invalid(format!("base.charge_rate must be at least 1, got {}", base.charge_rate));
+        }
+        if base.outpost_iron < 0 {
+            return invalid(format!("base.outpost_iron cannot be negative, got {}", base.outpost_iron));
+        }
+        if !(0..=OUTPOST_ENERGY_CAPACITY).contains(&base.outpost_energy) {
+            return invalid(format!("base.outpost_energy must be between 0 and {}, got {}", OUTPOST_ENERGY_CAPACITY, base.outpost_energy));
+        }
+        for (index, tier) in base.tiers.iter().enumerate() {
+            // Le premier niveau défini mène au niveau 2
+            tier.check().map_err(|e| ConfigError::Invalid(format!("base tier {}: {}", index + 2, e)))?;
+        }
+
+        let factory = &self.factory;
+        if factory.iron < 0 || factory.energy < 0 {
+            return invalid("factory costs cannot be negative".to_string());
+        }
+        if factory.build_ticks == 0 {
+            return invalid("factory.build_ticks must be positive".to_string());
+        }
+        if factory.queue == 0 {
+            return invalid("factory.queue must be at least 1".to_string());
+        }
+        self.energy.check().map_err(ConfigError::Invalid)?;
+        self.techs.check().map_err(ConfigError::Invalid)?;
+        if self.classes.iter().all(|&class| self.techs.locks_class(class)) {
+            return invalid("classes must contain at least one robot class not locked by the tech tree".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejects(config: &Config) -> bool {
+        matches!(config.validate(), Err(ConfigError::Invalid(_)))
+    }
+
+    #[test]
+    fn default_and_bundled_configs_are_valid() {
+        assert!(Config::default().validate().is_ok());
+        let bundled: Config = toml::from_str(include_str!("../../config.toml")).expect("config.toml parses");
+        assert!(bundled.validate().is_ok());
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        assert!(rejects(&Config { width: 2, ..Config::default() }));
+
+        let mut config = Config::default();
+        config.classes.clear();
+        assert!(rejects(&config));
+
+        let mut config = Config::default();
+        config.base.energy = config.base.energy_capacity + 1;
+        assert!(rejects(&config));
+
+        let mut config = Config::default();
+        config.base.docking_slots = 0;
+        assert!(rejects(&config));
+
+        let mut config = Config::default();
+        config.energy.scan = -1;
+        assert!(rejects(&config));
+    }
+
+    #[test]
+    fn cli_values_are_validated_after_the_file() {
+        let cli = Cli::parse_from(["robots", "--width", "2"]);
+        let mut config = Config::default();
+        config.apply_cli(&cli);
+        assert_eq!(config.width, 2);
+        assert!(rejects(&config));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("unknown_key = 1").is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use std::{io, path::PathBuf, thread, time::{Duration, Instant}, sync::{Arc, mpsc::{self, Receiver, RecvTimeoutError, Sender}}};
+use crate::base::research::TechState;
+use crate::maps::map::Map;
+use crate::robots::class::RobotClass;
+use super::replay::{Event, Recorder};
+use super::snapshot::Snapshot;
+use super::world::{World, TICK};
+
+// Au-delà de ce retard, la simulation renonce à rattraper les ticks manqués plutôt que de s'emballer
+const MAX_LAG: Duration = Duration::from_secs(1);
+
+// Demandes de l'interface, traitées par la simulation entre deux ticks
+pub enum Command {
+    Upgrade,
+    Order(RobotClass),
+    Research(usize),
+    Save(PathBuf),
+    Load(PathBuf),
+    Quit,
+}
+
+// Ce que la simulation publie : l'état du monde après chaque tick, ou un message pour l'utilisateur
+pub enum Update {
+    Tick(WorldView),
+    Status(String),
+}
+
+pub struct BaseView {
+    pub x: i32,
+    pub y: i32,
+    pub energy: i32,
+    pub energy_capacity: i32,
+    pub iron: i32,
+    pub iron_storage: i32,
+    pub research: i32,
+    pub research_storage: i32,
+    pub lvl: i32,
+    // Coût (énergie, fer, recherche) du prochain niveau, s'il en reste un
+    pub next_upgrade: Option<(i32, i32, i32)>,
+    pub docking_slots: i32,
+    pub charge_rate: i32,
+    pub outposts: Vec<(i32, i32)>,
+    pub max_outposts: usize,
+}
+
+pub struct RobotView {
+    pub x: i32,
+    pub y: i32,
+    pub energy: i32,
+    pub iron_collected: i32,
+    pub research_collected: i32,
+    pub cargo: i32,
+    pub cargo_capacity: i32,
+    pub class: RobotClass,
+}
+
+// Image figée du monde pour l'affichage : la carte est partagée d'une image à l'autre tant qu'aucun gisement ne change
+pub struct WorldView {
+    pub tick: u64,
+    pub map: Arc<Map>,
+    pub base: BaseView,
+    pub robots: Vec<RobotView>,
+    pub factory: FactoryView,
+    pub research: Vec<TechView>,
+}
+
+pub struct FactoryView {
+    pub iron: i32,
+    pub energy: i32,
+    // Classe de chaque commande et ticks restants avant sa livraison
+    pub queue: Vec<(RobotClass, u32)>,
+    pub max_queue: usize,
+}
+
+pub struct TechView {
+    pub name: String,
+    pub cost: i32,
+    pub progress: i32,
+    pub state: TechState,
+}
+
+impl WorldView {
+    fn new(world: &World, map: Arc<Map>) -> Self {
+        let base = &world.base;
+        Self {
+            tick: world.tick,
+            map,
+            base: BaseView {
+                x: base.x,
+                y: base.y,
+                energy: base.energy,
+                energy_capacity: base.energy_capacity,
+                iron: base.iron,
+                iron_storage: base.iron_storage,
+                research: base.research,
+                research_storage: base.research_storage,
+                lvl: base.lvl,
+                next_upgrade: base.next_tier().map(|tier| tier.cost(base.upgrade_discount)),
+                docking_slots: base.docking_slots,
+                charge_rate: base.charge_rate,
+                outposts: base.outposts.iter().map(|outpost| (outpost.x, outpost.y)).collect(),
+                max_outposts: base.max_outposts,
+            },
+            robots: world.robots.iter().map(|robot| RobotView {
+                x: robot.x,
+                y: robot.y,
+                energy: robot.energy,
+                iron_collected: robot.iron_collected,
+                research_collected: robot.research_collected,
+                cargo: robot.cargo(),
+                cargo_capacity: robot.cargo_capacity(),
+                class: robot.class,
+            }).collect(),
+            factory: FactoryView {
+                iron: world.factory.iron,
+                energy: world.factory.energy,
+                queue: world.factory.delivery_ticks(),
+                max_queue: world.factory.max_queue,
+            },
+            research: world.research.techs.iter().enumerate().map(|(index, tech)| TechView {
+                name: tech.name.clone(),
+                cost: tech.cost,
+                progress: world.research.progress[index],
+                state: world.research.state(index),
+            }).collect(),
+        }
+    }
+}
+
+// Lance la simulation dans son propre thread, au rythme d'un tick par TICK quelle que soit la cadence de l'affichage.
+// Le thread s'arrête sur Command::Quit ou quand l'interface a disparu, et rend le résultat de l'enregistrement
+pub fn spawn(world: World, recorder: Option<Recorder>) -> (Sender<Command>, Receiver<Update>, thread::JoinHandle<io::Result<()>>) {
+    let (command_sender, commands) = mpsc::channel();
+    let (updates, update_receiver) = mpsc::channel();
+    let handle = thread::spawn(move || run(world, recorder, commands, updates));
+    (command_sender, update_receiver, handle)
+}
+
+fn run(mut world: World, mut recorder: Option<Recorder>, commands: Receiver<Command>, updates: Sender<Update>) -> io::Result<()> {
+    let mut map = Arc::new(world.map.clone());
+    if updates.send(Update::Tick(WorldView::new(&world, Arc::clone(&map)))).is_err() {
+        return Ok(());
+    }
+    let mut next_tick = Instant::now() + TICK;
+
+    loop {
+        let command = match commands.recv_timeout(next_tick.saturating_duration_since(Instant::now())) {
+            Ok(command) => Some(command),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        let status = match command {
+            Some(Command::Quit) => break,
+            Some(Command::Upgrade) => {
+                let cost = world.base.next_tier().map_or((0, 0, 0), |tier| tier.cost(world.base.upgrade_discount));
+                Some(match world.base.upgrade_base() {
+                    Ok(lvl) => {
+                        if let Some(recorder) = recorder.as_mut() {
+                            let (energy, iron, research) = cost;
+                            recorder.record(&[Event::Upgraded { lvl, energy, iron, research }]);
+                        }
+                        format!("Base upgraded to level {}", lvl)
+                    },
+                    Err(e) => format!("Cannot upgrade: {}", e),
+                })
+            },
+            Some(Command::Order(class)) => Some(match world.factory.order(class, &mut world.base) {
+                Ok(()) => {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.record(&[Event::Ordered { class, iron: world.factory.iron, energy: world.factory.energy }]);
+                    }
+                    format!("Ordered {}", class)
+                },
+                Err(e) => format!("Cannot order {}: {}", class, e),
+            }),
+            Some(Command::Research(index)) => Some(match world.research.select(index) {
+                Ok(name) => format!("Researching {}", name),
+                Err(e) => format!("Cannot research: {}", e),
+            }),
+            Some(Command::Save(path)) => Some(match world.snapshot().save(&path) {
+                Ok(()) => format!("Saved to {}", path.display()),
+                Err(e) => format!("Save failed: {}", e),
+            }),
+            Some(Command::Load(path)) => Some(match Snapshot::load(&path) {
+                Ok(snapshot) => {
+                    world = World::from_snapshot(snapshot);
+                    map = Arc::new(world.map.clone());
+                    next_tick = Instant::now() + TICK;
+                    let mut status = format!("Loaded {}", path.display());
+                    // L'enregistrement en cours ne correspond plus au monde chargé
+                    if let Some(recorder) = recorder.take() {
+                        let _ = recorder.finish();
+                        status.push_str(" (recording stopped)");
+                    }
+                    status
+                },
+                Err(e) => format!("Load failed: {}", e),
+            }),
+            None => {
+                let events = world.step();
+                // Seule une extraction modifie la carte : les autres ticks réutilisent la copie déjà publiée
+                if events.iter().any(|event| matches!(event, Event::Collected { .. })) {
+                    map = Arc::new(world.map.clone());
+                }
+                let recorded = recorder.as_mut().map(|recorder| {
+                    recorder.record(&events);
+                    recorder.end_tick(world.tick)
+                });
+                if let Some(Err(e)) = recorded {
+                    recorder = None;
+                    let _ = updates.send(Update::Status(format!("Recording failed: {}", e)));
+                }
+                next_tick += TICK;
+                let now = Instant::now();
+                if now > next_tick + MAX_LAG {
+                    next_tick = now;
+                }
+                None
+            },
+        };
+
+        if let Some(status) = status {
+            if updates.send(Update::Status(status)).is_err() {
+                break;
+            }
+        }
+        if updates.send(Update::Tick(WorldView::new(&world, Arc::clone(&map)))).is_err() {
+            break;
+        }
+    }
+
+    match recorder {
+        Some(recorder) => recorder.finish(),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,5 @@
+pub mod config;
+pub mod live;
+pub mod replay;
+pub mod snapshot;
+pub mod world;
@@ -0,0 +1,357 @@
+use std::{fs::File, io::{self, BufRead, BufReader, BufWriter, Write}, path::Path};
+use serde::{Deserialize, Serialize};
+use crate::maps::map::{Map, Resource};
+use crate::robots::robot::{Robot, BATTERY_CAPACITY};
+use crate::robots::class::RobotClass;
+use crate::base::base::{Base, Outpost};
+use super::snapshot::SnapshotError;
+
+// Version du format du journal, écrite dans l'en-tête : un journal d'une autre version est refusé à la lecture
+// plutôt que relu de travers. À augmenter à chaque changement des événements ou de l'image de départ
+const FORMAT_VERSION: u32 = 5;
+
+// Noms courts pour garder le journal compact (une ligne JSON par tick non vide)
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum Event {
+    #[serde(rename = "m")]
+    Moved { id: i32, x: i32, y: i32, energy: i32 },
+    // Énergie d'un robot qui a changé sans qu'il change de case (extraction, relevé, attente, recharge)
+    #[serde(rename = "e")]
+    Energy { id: i32, energy: i32 },
+    #[serde(rename = "c")]
+    Collected { id: i32, x: i32, y: i32, resource: Resource, amount: i32 },
+    #[serde(rename = "d")]
+    Deposited { id: i32, iron: i32, research: i32 },
+    #[serde(rename = "x")]
+    Discarded { id: i32, iron: i32, research: i32 },
+    // Amélioration payée sur les réserves de la base, au coût remisé par la recherche
+    #[serde(rename = "u")]
+    Upgraded { lvl: i32, energy: i32, iron: i32, research: i32 },
+    #[serde(rename = "o")]
+    Built { id: i32, x: i32, y: i32 },
+    // Commande passée à l'usine, payée tout de suite : la relecture retire son coût des réserves de la base
+    #[serde(rename = "q")]
+    Ordered { class: RobotClass, iron: i32, energy: i32 },
+    #[serde(rename = "p")]
+    Produced { id: i32, class: RobotClass },
+    // Projet terminé : les points investis au fil des ticks sont suivis par Stock
+    #[serde(rename = "r")]
+    Researched { tech: usize },
+    // Énergie et recherche de la base en fin de tick, quand elles ont changé (production, recharges, recherche)
+    #[serde(rename = "s")]
+    Stock { energy: i32, research: i32 },
+}
+
+impl Event {
+    // Case désignée par l'événement, à vérifier avant de l'appliquer à la carte
+    fn cell(&self) -> Option<(i32, i32)> {
+        match *self {
+            Event::Moved { x, y, .. } | Event::Collected { x, y, .. } | Event::Built { x, y, .. } => Some((x, y)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RobotFrame {
+    pub id: i32,
+    pub x: i32,
+    pub y: i32,
+    pub energy: i32,
+    pub iron_collected: i32,
+    pub research_collected: i32,
+    pub class: RobotClass,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Frame {
+    pub tick: u64,
+    pub map: Map,
+    pub base: Base,
+    pub robots: Vec<RobotFrame>,
+}
+
+impl Frame {
+    pub fn new(tick: u64, map: &Map, base: &Base, robots: &[Robot]) -> Self {
+        Self {
+            tick,
+            map: map.clone(),
+            base: base.clone(),
+            robots: robots.iter().map(|robot| RobotFrame {
+                id: robot.id,
+                x: robot.x,
+                y: robot.y,
+                energy: robot.energy,
+                iron_collected: robot.iron_collected,
+                research_collected: robot.research_collected,
+                class: robot.class,
+            }).collect(),
+        }
+    }
+
+    fn in_bounds(&self, (x, y): (i32, i32)) -> bool {
+        x >= 0 && y >= 0 && x < self.map.width && y < self.map.height
+    }
+
+    // Un journal tronqué ou modifié à la main ne doit pas faire planter la relecture
+    fn check(&self) -> Result<(), SnapshotError> {
+        let has_dims = self.map.blueprint.len() == self.map.width as usize
+            && self.map.blueprint.iter().all(|column| column.len() == self.map.height as usize);
+        if self.map.width <= 0 || self.map.height <= 0 || !has_dims {
+            return Err(SnapshotError::Invalid("map dimensions do not match its tiles".to_string()));
+        }
+        if !self.base.docks().all(|dock| self.in_bounds(dock)) {
+            return Err(SnapshotError::Invalid("base or outpost is outside the map".to_string()));
+        }
+        if let Some(robot) = self.robots.iter().find(|robot| !self.in_bounds((robot.x, robot.y))) {
+            return Err(SnapshotError::Invalid(format!("robot {} is outside the map", robot.id)));
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, event: &Event) {
+        match *event {
+            Event::Moved { id, x, y, energy } => {
+                if let Some(robot) = self.robots.iter_mut().find(|r| r.id == id) {
+                    robot.x = x;
+                    robot.y = y;
+                    robot.energy = energy;
+                }
+            },
+            Event::Energy { id, energy } => {
+                if let Some(robot) = self.robots.iter_mut().find(|r| r.id == id) {
+                    robot.energy = energy;
+                }
+            },
+            Event::Collected { id, x, y, resource, amount } => {
+                self.map.extract(x as usize, y as usize, amount);
+                if let Some(robot) = self.robots.iter_mut().find(|r| r.id == id) {
+                    match resource {
+                        Resource::Iron => robot.iron_collected += amount,
+                        Resource::Research => robot.research_collected += amount,
+                        Resource::None => {},
+                    }
+                }
+            },
+            Event::Deposited { id, iron, research } => {
+                self.base.iron += iron;
+                self.base.research += research;
+                if let Some(robot) = self.robots.iter_mut().find(|r| r.id == id) {
+                    robot.iron_collected -= iron;
+                    robot.research_collected -= research;
+                }
+            },
+            Event::Discarded { id, iron, research } => {
+                if let Some(robot) = self.robots.iter_mut().find(|r| r.id == id) {
+                    robot.iron_collected -= iron;
+                    robot.research_collected -= research;
+                }
+            },
+            Event::Upgraded { lvl, energy, iron, research } => {
+                self.base.energy -= energy;
+                self.base.iron -= iron;
+                self.base.research -= research;
+                // Les réserves agrandies par ce niveau s'affichent sans attendre l'image clé suivante
+                if let Some(tier) = self.base.next_tier().cloned() {
+                    tier.apply(&mut self.base);
+                }
+                self.base.lvl = lvl;
+            },
+            Event::Ordered { iron, energy, .. } => {
+                self.base.iron -= iron;
+                self.base.energy -= energy;
+            },
+            Event::Produced { id, class } => self.robots.push(RobotFrame {
+                id,
+                x: self.base.x,
+                y: self.base.y,
+                energy: BATTERY_CAPACITY,
+                iron_collected: 0,
+                research_collected: 0,
+                class,
+            }),
+            Event::Researched { .. } => {},
+            Event::Stock { energy, research } => {
+                self.base.energy = energy;
+                self.base.research = research;
+            },
+            Event::Built { x, y, .. } => {
+                self.base.iron -= self.base.outpost_iron;
+                self.base.energy -= self.base.outpost_energy;
+                self.base.outposts.push(Outpost { x, y, energy: self.base.outpost_energy });
+            },
+        }
+    }
+}
+
+// Première ligne du journal : version du format et image de départ
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    #[serde(flatten)]
+    frame: Frame,
+}
+
+// Lue avant l'en-tête complet, pour refuser proprement un journal d'un autre format
+#[derive(Deserialize)]
+struct Version {
+    #[serde(default)]
+    version: u32,
+}
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+    pending: Vec<Event>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, initial: &Frame) -> Result<Self, SnapshotError> {
+        let file = File::create(path).map_err(SnapshotError::Io)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &Header { version: FORMAT_VERSION, frame: initial.clone() }).map_err(SnapshotError::Format)?;
+        writeln!(writer).map_err(SnapshotError::Io)?;
+        Ok(Self { writer, pending: Vec::new() })
+    }
+
+    pub fn record(&mut self, events: &[Event]) {
+        self.pending.extend_from_slice(events);
+    }
+
+    // Écrit les événements accumulés comme appartenant au tick donné
+    pub fn end_tick(&mut self, tick: u64) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        serde_json::to_writer(&mut self.writer, &(tick, &self.pending))?;
+        writeln!(self.writer)?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub struct Replay {
+    initial: Frame,
+    ticks: Vec<(u64, Vec<Event>)>,
+    next: usize,
+    pub state: Frame,
+    pub last_tick: u64,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let file = File::open(path).map_err(SnapshotError::Io)?;
+        let mut lines = BufReader::new(file).lines();
+
+        let header = lines.next()
+            .ok_or_else(|| SnapshotError::Invalid("empty recording".to_string()))?
+            .map_err(SnapshotError::Io)?;
+        let Version { version } = serde_json::from_str(&header).map_err(SnapshotError::Format)?;
+        if version != FORMAT_VERSION {
+            return Err(SnapshotError::Invalid(format!("recording format version {} is not supported (expected {})", version, FORMAT_VERSION)));
+        }
+        let Header { frame: initial, .. } = serde_json::from_str(&header).map_err(SnapshotError::Format)?;
+        initial.check()?;
+
+        let mut ticks: Vec<(u64, Vec<Event>)> = Vec::new();
+        for line in lines {
+            let line = line.map_err(SnapshotError::Io)?;
+            if line.is_empty() {
+                continue;
+            }
+            let entry: (u64, Vec<Event>) = serde_json::from_str(&line).map_err(SnapshotError::Format)?;
+            if ticks.last().is_some_and(|(tick, _)| *tick > entry.0) || entry.0 < initial.tick {
+                return Err(SnapshotError::Invalid(format!("tick {} is out of order", entry.0)));
+            }
+            if entry.1.iter().filter_map(Event::cell).any(|cell| !initial.in_bounds(cell)) {
+                return Err(SnapshotError::Invalid(format!("tick {} refers to a tile outside the map", entry.0)));
+            }
+            ticks.push(entry);
+        }
+
+        let last_tick = ticks.last().map_or(initial.tick, |(tick, _)| *tick);
+        Ok(Self {
+            state: initial.clone(),
+            initial,
+            ticks,
+            next: 0,
+            last_tick,
+        })
+    }
+
+    pub fn first_tick(&self) -> u64 {
+        self.initial.tick
+    }
+
+    pub fn seek(&mut self, tick: u64) {
+        let tick = tick.clamp(self.initial.tick, self.last_tick);
+        if tick < self.state.tick {
+            self.state = self.initial.clone();
+            self.next = 0;
+        }
+        while let Some((event_tick, events)) = self.ticks.get(self.next) {
+            if *event_tick > tick {
+                break;
+            }
+            for event in events {
+                self.state.apply(event);
+            }
+            self.next += 1;
+        }
+        self.state.tick = tick;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::simulation::config::Config;
+    use crate::simulation::world::World;
+
+    fn summary(frame: &Frame) -> String {
+        let base = (frame.base.energy, frame.base.iron, frame.base.research, frame.base.lvl, frame.base.outposts.len());
+        serde_json::to_string(&(base, &frame.robots, &frame.map)).expect("frame serializes")
+    }
+
+    #[test]
+    fn seek_rebuilds_the_recorded_frames_in_both_directions() {
+        let config = Config { seed: 5, width: 50, height: 30, num_robots: 4, ..Config::default() };
+        let mut world = World::new(&config);
+        let path = std::env::temp_dir().join(format!("replay-test-{}.jsonl", std::process::id()));
+        let mut recorder = Recorder::create(&path, &world.frame()).expect("recording starts");
+        let mut frames = Vec::new();
+        while frames.len() < 3 {
+            let events = world.step();
+            recorder.record(&events);
+            recorder.end_tick(world.tick).expect("tick is written");
+            // La relecture s'arrête au dernier tick enregistré : les images de référence tombent sur des ticks avec événements
+            if !events.is_empty() && world.tick >= 500 * (frames.len() as u64 + 1) {
+                frames.push(world.frame());
+            }
+        }
+        recorder.finish().expect("recording ends");
+        let replay = Replay::load(&path);
+        fs::remove_file(&path).ok();
+        let mut replay = replay.expect("recording loads");
+
+        for index in [2, 0, 1, 2] {
+            let expected = &frames[index];
+            replay.seek(expected.tick);
+            assert_eq!(replay.state.tick, expected.tick);
+            assert!(summary(&replay.state) == summary(expected), "tick {}", expected.tick);
+        }
+    }
+
+    #[test]
+    fn recordings_from_another_version_are_rejected() {
+        let path = std::env::temp_dir().join(format!("replay-version-{}.jsonl", std::process::id()));
+        fs::write(&path, "{\"version\":1}\n").expect("file is written");
+        let replay = Replay::load(&path);
+        fs::remove_file(&path).ok();
+        assert!(matches!(replay, Err(SnapshotError::Invalid(_))));
+    }
+}
@@ -0,0 +1,144 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+use serde::{Deserialize, Serialize};
+use crate::maps::map::Map;
+use crate::robots::robot::Robot;
+use crate::base::base::Base;
+use crate::base::factory::Factory;
+use crate::base::research::Research;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub map: Map,
+    pub base: Base,
+    pub robots: Vec<Robot>,
+    pub factory: Factory,
+    pub research: Research,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot I/O error: {}", e),
+            SnapshotError::Format(e) => write!(f, "malformed snapshot: {}", e),
+            SnapshotError::Invalid(msg) => write!(f, "inconsistent snapshot: {}", msg),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl Snapshot {
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let json = serde_json::to_string(self).map_err(SnapshotError::Format)?;
+        fs::write(path, json).map_err(SnapshotError::Io)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let content = fs::read_to_string(path).map_err(SnapshotError::Io)?;
+        let snapshot: Snapshot = serde_json::from_str(&content).map_err(SnapshotError::Format)?;
+        snapshot.check()?;
+        Ok(snapshot)
+    }
+
+    fn check(&self) -> Result<(), SnapshotError> {
+        let in_bounds = |x: i32, y: i32| x >= 0 && y >= 0 && x < self.map.width && y < self.map.height;
+        let has_dims = |map: &Map| {
+            map.blueprint.len() == self.map.width as usize
+                && map.blueprint.iter().all(|column| column.len() == self.map.height as usize)
+        };
+
+        if self.map.width <= 0 || self.map.height <= 0 || !has_dims(&self.map) {
+            return Err(SnapshotError::Invalid("map dimensions do not match its tiles".to_string()));
+        }
+        if !has_dims(&self.base.known_map) {
+            return Err(SnapshotError::Invalid("base known map has wrong dimensions".to_string()));
+        }
+        if self.factory.strategies.is_empty() {
+            return Err(SnapshotError::Invalid("factory has no strategy to give its robots".to_string()));
+        }
+        if !in_bounds(self.base.x, self.base.y) {
+            return Err(SnapshotError::Invalid("base is outside the map".to_string()));
+        }
+        if self.base.lvl < 1 {
+            return Err(SnapshotError::Invalid(format!("base level must be at least 1, got {}", self.base.lvl)));
+        }
+        if self.base.outposts.iter().any(|outpost| !in_bounds(outpost.x, outpost.y)) {
+            return Err(SnapshotError::Invalid("an outpost is outside the map".to_string()));
+        }
+        let research = &self.research;
+        if research.progress.len() != research.techs.len() || research.active.is_some_and(|index| index >= research.techs.len()) {
+            return Err(SnapshotError::Invalid("research progress does not match its tech tree".to_string()));
+        }
+        for robot in &self.robots {
+            if !in_bounds(robot.x, robot.y) {
+                return Err(SnapshotError::Invalid(format!("robot {} is outside the map", robot.id)));
+            }
+            if !has_dims(&robot.known_map) {
+                return Err(SnapshotError::Invalid(format!("robot {} known map has wrong dimensions", robot.id)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::config::Config;
+    use crate::simulation::world::World;
+
+    fn run(world: &mut World, ticks: u64) -> String {
+        for _ in 0..ticks {
+            world.step();
+        }
+        serde_json::to_string(&world.snapshot()).expect("snapshot serializes")
+    }
+
+    #[test]
+    fn resumed_world_matches_the_continuous_run() {
+        let config = Config { seed: 3, width: 50, height: 30, num_robots: 4, collisions: true, ..Config::default() };
+        let mut world = World::new(&config);
+        run(&mut world, 600);
+
+        let path = std::env::temp_dir().join(format!("snapshot-test-{}.json", std::process::id()));
+        world.snapshot().save(&path).expect("snapshot saves");
+        let loaded = Snapshot::load(&path);
+        fs::remove_file(&path).ok();
+        let mut resumed = World::from_snapshot(loaded.expect("snapshot loads"));
+
+        assert!(run(&mut world, 400) == run(&mut resumed, 400));
+    }
+
+    #[test]
+    fn inconsistent_snapshots_are_rejected() {
+        let world = World::new(&Config { width: 20, height: 20, ..Config::default() });
+
+        let mut snapshot = world.snapshot();
+        snapshot.base.lvl = 0;
+        assert!(matches!(snapshot.check(), Err(SnapshotError::Invalid(_))));
+
+        let mut snapshot = world.snapshot();
+        snapshot.robots[0].x = snapshot.map.width;
+        assert!(matches!(snapshot.check(), Err(SnapshotError::Invalid(_))));
+
+        let mut snapshot = world.snapshot();
+        snapshot.research.progress.pop();
+        assert!(matches!(snapshot.check(), Err(SnapshotError::Invalid(_))));
+
+        let mut snapshot = world.snapshot();
+        snapshot.base.known_map.blueprint.pop();
+        assert!(matches!(snapshot.check(), Err(SnapshotError::Invalid(_))));
+
+        let mut snapshot = world.snapshot();
+        snapshot.factory.strategies.clear();
+        assert!(matches!(snapshot.check(), Err(SnapshotError::Invalid(_))));
+    }
+}
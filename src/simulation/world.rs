@@ -0,0 +1,275 @@
+use std::time::Duration;
+use crate::maps::map::{self, Map, Resource, Topology};
+use rayon::prelude::*;
+use crate::robots::robot::{Robot, ACTION_TICKS};
+use crate::robots::strategy::Action;
+use crate::base::base::Base;
+use crate::base::factory::Factory;
+use crate::base::research::{Research, TechState};
+use crate::robots::class::RobotClass;
+use crate::robots::energy::EnergyCosts;
+use crate::robots::strategy::StrategyKind;
+use crate::robots::traffic::Priority;
+use super::config::Config;
+use super::snapshot::{Snapshot, SnapshotError};
+use super::replay::{Event, Frame, Recorder};
+
+// Durée logique d'un tick
+pub const TICK: Duration = Duration::from_millis(100);
+
+pub struct World {
+    pub map: Map,
+    pub base: Base,
+    pub robots: Vec<Robot>,
+    pub factory: Factory,
+    pub research: Research,
+    pub tick: u64,
+}
+
+impl World {
+    pub fn new(config: &Config) -> Self {
+        let (map, _noise_map) = map::generate_map(config.seed, config.width, config.height);
+        let mut base = Base::new(&map, &config.base);
+        let research = Research::new(config.techs.clone());
+        research.lock(&mut base);
+        let robots = spawn_robots(config, &map, &base.locked_classes);
+        Self {
+            map,
+            base,
+            robots,
+            factory: Factory::new(config),
+            research,
+            tick: 0,
+        }
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot) -> Self {
+        Self {
+            map: snapshot.map,
+            base: snapshot.base,
+            robots: snapshot.robots,
+            factory: snapshot.factory,
+            research: snapshot.research,
+            tick: snapshot.tick,
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tick: self.tick,
+            map: self.map.clone(),
+            base: self.base.clone(),
+            robots: self.robots.clone(),
+            factory: self.factory.clone(),
+            research: self.research.clone(),
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        Frame::new(self.tick, &self.map, &self.base, &self.robots)
+    }
+
+    // Un tick se déroule en phases, toujours dans le même ordre :
+    // 1. perception : chaque robot découvre les cases autour de lui (en parallèle, la carte n'est que lue)
+    // 2. quai : les robots prêts à agir et arrivés à la base déposent, échangent leur carte et se rechargent
+    //    (ceux qui sont au-delà de la carte de la base préparent ensuite leur propre champ de retour, en parallèle)
+    // 3. décision : chaque robot prêt choisit son action d'après la base telle qu'elle est (en parallèle)
+    // 4. action : les robots réservent leur cible et se déplacent, un par un dans l'ordre de leurs identifiants
+    // 5. construction : un robot qui mine loin de tout point d'amarrage peut y bâtir un avant-poste, dans le même ordre
+    // 6. extraction : les robots sur un gisement en extraient
+    // 7. base : production d'énergie, à la base comme aux avant-postes
+    // 8. usine : la commande en cours avance, et le robot terminé rejoint les autres à la base
+    // 9. recherche : le projet en cours reçoit des points de recherche des réserves
+    // Les phases séquentielles gardent la simulation déterministe, quel que soit le nombre de threads
+    pub fn step(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        let tick = self.tick;
+        // Énergie de chaque robot et réserves de la base telles que le journal les connaît
+        let mut reported: Vec<i32> = self.robots.iter().map(|robot| robot.energy).collect();
+        let stock = (self.base.energy, self.base.research);
+
+        let map = &self.map;
+        self.robots.par_iter_mut().for_each(|robot| robot.discover_current_location(map, tick));
+
+        let ready: Vec<bool> = self.robots.iter_mut().map(|robot| robot.ready(TICK)).collect();
+        for (robot, _) in self.robots.iter_mut().zip(&ready).filter(|(_, &ready)| ready) {
+            events.extend(dock(robot, &mut self.base));
+            self.base.prepare_return_field(robot.modules, robot.topology);
+        }
+        let base = &self.base;
+        self.robots.par_iter_mut().zip(&ready)
+            .filter(|(_, &ready)| ready)
+            .for_each(|(robot, _)| robot.prepare_home_field(base));
+
+        let decisions: Vec<Option<Action>> = self.robots.par_iter().zip(&ready)
+            .map(|(robot, &ready)| if ready { robot.decide(base) } else { None })
+            .collect();
+
+        for (((robot, decision), _), reported) in self.robots.iter_mut().zip(decisions).zip(&ready).zip(&mut reported).filter(|((_, &ready), _)| ready) {
+            let previous = (robot.x, robot.y);
+            robot.act(decision, &mut self.base, tick);
+            if (robot.x, robot.y) != previous {
+                events.push(Event::Moved { id: robot.id, x: robot.x, y: robot.y, energy: robot.energy });
+                *reported = robot.energy;
+            }
+        }
+
+        for robot in self.robots.iter() {
+            if let Some((x, y)) = self.base.build_outpost(robot) {
+                events.push(Event::Built { id: robot.id, x, y });
+            }
+        }
+
+        for robot in self.robots.iter_mut() {
+            events.extend(mine(robot, &mut self.map, &mut self.base, tick));
+        }
+
+        // Dépenses et recharges sans changement de case : la relecture suit ainsi la batterie à chaque tick
+        for (robot, &reported) in self.robots.iter().zip(&reported) {
+            if robot.energy != reported {
+                events.push(Event::Energy { id: robot.id, energy: robot.energy });
+            }
+        }
+
+        self.base.generate_energy();
+
+        // Avec les collisions, le nouveau robot attend une place libre à quai et la réserve aussitôt
+        let id = self.robots.iter().map(|robot| robot.id + 1).max().unwrap_or(0);
+        let (dock, slot) = ((self.base.x, self.base.y), tick / ACTION_TICKS);
+        let docking = !self.factory.collisions || self.base.traffic.is_free(&self.base, dock, slot, id, slot, Priority::Normal);
+        if let Some(class) = self.factory.advance(docking) {
+            let strategy = self.factory.next_strategy();
+            self.robots.push(new_robot(&self.map, class, id, strategy, self.factory.topology, self.factory.collisions, self.factory.energy_costs));
+            if self.factory.collisions {
+                self.base.traffic.hold(id, dock, slot, Priority::Normal);
+            }
+            events.push(Event::Produced { id, class });
+        }
+
+        if let Some(tech) = self.research.advance(&mut self.base) {
+            events.push(Event::Researched { tech });
+        }
+        if (self.base.energy, self.base.research) != stock {
+            events.push(Event::Stock { energy: self.base.energy, research: self.base.research });
+        }
+
+        self.tick += 1;
+        events
+    }
+}
+
+// Les classes verrouillées par l'arbre de recherche sont sautées: la configuration en garantit au moins une disponible
+pub fn spawn_robots(config: &Config, map: &Map, locked: &[RobotClass]) -> Vec<Robot> {
+    let classes: Vec<RobotClass> = config.classes.iter().copied().filter(|class| !locked.contains(class)).collect();
+    (0..config.num_robots)
+        .map(|i| {
+            let class = classes[i % classes.len()];
+            let strategy = config.strategies[i % config.strategies.len()];
+            new_robot(map, class, i as i32, strategy, config.topology, config.collisions, config.energy)
+        })
+        .collect()
+}
+
+// Robot neuf à la base, qu'il soit créé au départ ou sorti de l'usine
+fn new_robot(map: &Map, class: RobotClass, id: i32, strategy: StrategyKind, topology: Topology, collisions: bool, energy_costs: EnergyCosts) -> Robot {
+    let mut robot = Robot::new(map.width, map.height, map, class);
+    robot.strategy = strategy;
+    robot.topology = topology;
+    robot.avoid_collisions = collisions;
+    robot.energy_costs = energy_costs;
+    robot.id = id;
+    robot
+}
+
+// Passage à quai : dépôts dans les réserves de la base, et cargaison perdue selon la politique de débordement
+fn dock(robot: &mut Robot, base: &mut Base) -> Vec<Event> {
+    let mut events = Vec::new();
+    let (previous_iron, previous_research) = (base.iron, base.research);
+    let (previous_cargo_iron, previous_cargo_research) = (robot.iron_collected, robot.research_collected);
+
+    robot.dock(base);
+
+    let (deposited_iron, deposited_research) = (base.iron - previous_iron, base.research - previous_research);
+    if deposited_iron != 0 || deposited_research != 0 {
+        events.push(Event::Deposited { id: robot.id, iron: deposited_iron, research: deposited_research });
+    }
+    let discarded_iron = previous_cargo_iron - robot.iron_collected - deposited_iron;
+    let discarded_research = previous_cargo_research - robot.research_collected - deposited_research;
+    if discarded_iron > 0 || discarded_research > 0 {
+        events.push(Event::Discarded { id: robot.id, iron: discarded_iron, research: discarded_research });
+    }
+    events
+}
+
+// Rien n'est extrait une fois la soute pleine (toujours le cas pour un explorateur, sans soute)
+fn mine(robot: &mut Robot, map: &mut Map, base: &mut Base, tick: u64) -> Option<Event> {
+    let (resource, amount) = robot.mine(map, tick)?;
+    if map.blueprint[robot.x as usize][robot.y as usize].resource == Resource::None {
+        base.release_at((robot.x, robot.y));
+    }
+    Some(Event::Collected { id: robot.id, x: robot.x, y: robot.y, resource, amount })
+}
+
+// Mode sans terminal : avance le monde d'un nombre fixe de ticks, sans attente
+pub fn run_headless(config: &Config, world: &mut World, mut recorder: Option<Recorder>) -> Result<(), SnapshotError> {
+    for _ in 0..config.ticks {
+        let events = world.step();
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(&events);
+            recorder.end_tick(world.tick).map_err(SnapshotError::Io)?;
+        }
+    }
+    if let Some(recorder) = recorder {
+        recorder.finish().map_err(SnapshotError::Io)?;
+    }
+
+    let base = &world.base;
+    println!("seed: {} | ticks: {} | robots: {}", world.map.seed, world.tick, world.robots.len());
+    println!(
+        "base: energy {} / {} | iron {} | research {} | lvl {} | outposts {}",
+        base.energy, base.energy_capacity, base.iron, base.research, base.lvl, base.outposts.len()
+    );
+    for outpost in &base.outposts {
+        println!("outpost: ({}, {}) energy {}", outpost.x, outpost.y, outpost.energy);
+    }
+    let research = &world.research;
+    for (index, tech) in research.techs.iter().enumerate().filter(|&(index, _)| research.state(index) == TechState::Done) {
+        println!("researched: {} ({})", tech.name, index);
+    }
+    for robot in &world.robots {
+        println!(
+            "robot {}: ({}, {}) energy {} iron {} research {} cargo {} / {} class {} strategy {}",
+            robot.id, robot.x, robot.y, robot.energy, robot.iron_collected, robot.research_collected,
+            robot.cargo(), robot.cargo_capacity(),
+            robot.class, robot.strategy
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(config: &Config, threads: usize) -> (i32, i32, i32, Vec<(i32, i32, i32)>) {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().expect("thread pool");
+        pool.install(|| {
+            let mut world = World::new(config);
+            for _ in 0..1500 {
+                world.step();
+            }
+            let robots = world.robots.iter().map(|robot| (robot.x, robot.y, robot.energy)).collect();
+            (world.base.iron, world.base.research, world.base.energy, robots)
+        })
+    }
+
+    #[test]
+    fn same_seed_gives_same_totals_whatever_the_thread_count() {
+        for collisions in [false, true] {
+            let config = Config { seed: 7, width: 60, height: 40, num_robots: 6, collisions, ..Config::default() };
+            let single = totals(&config, 1);
+            assert!(single.0 + single.1 > 0, "nothing was collected");
+            assert!(single == totals(&config, 4), "collisions: {}", collisions);
+        }
+    }
+}